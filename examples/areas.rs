@@ -30,7 +30,7 @@ fn main() -> Result<(), Error> {
             } else {
                 "p"
             },
-            format!(
+            format_args!(
                 " {:x} {}",
                 area.file_offset().unwrap_or(0),
                 area.path()
@@ -1,5 +1,6 @@
 use crate::error::Error;
 use bitflags::bitflags;
+use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -40,6 +41,32 @@ pub enum ShareMode {
     Shared,
 }
 
+/// The kind of the memory area, i.e. what the memory area is used for or backed by.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum MemoryAreaKind {
+    /// The memory area is backed by a file.
+    File,
+    /// The memory area is the heap of the process.
+    Heap,
+    /// The memory area is the stack of the main thread or of another thread.
+    Stack,
+    /// The memory area contains the virtual dynamic shared object.
+    Vdso,
+    /// The memory area contains the variables used by the virtual dynamic shared object.
+    Vvar,
+    /// The memory area contains the legacy virtual system call page.
+    Vsyscall,
+    /// The memory area is anonymous memory that has been named, e.g. using `PR_SET_VMA`.
+    AnonNamed(String),
+    /// The memory area is anonymous memory.
+    Anonymous,
+    /// The memory area is backed by a file that has since been deleted.
+    Deleted,
+    /// The memory area is a special memory area of the given name that is not covered by the
+    /// other kinds, e.g. `[uprobes]`.
+    Other(String),
+}
+
 /// Describes a memory area of a process.
 #[derive(Clone, Debug)]
 pub struct MemoryArea {
@@ -53,6 +80,12 @@ pub struct MemoryArea {
     pub(crate) share_mode: ShareMode,
     /// The path to the file that backs this memory area, if backed by a file.
     pub(crate) path: Option<(PathBuf, u64)>,
+    /// The major and minor number of the device containing the file that backs this memory area.
+    pub(crate) device: (u32, u32),
+    /// The inode of the file that backs this memory area.
+    pub(crate) inode: u64,
+    /// The kind of the memory area.
+    pub(crate) kind: MemoryAreaKind,
 }
 
 impl MemoryArea {
@@ -103,6 +136,183 @@ impl MemoryArea {
     pub fn file_offset(&self) -> Option<u64> {
         self.path.as_ref().map(|(_, offset)| *offset)
     }
+
+    /// The major and minor number of the device containing the file that backs this memory area.
+    /// This is `(0, 0)` if the memory area is not backed by a file, or if the current platform
+    /// does not provide this information. Only Linux provides this information.
+    #[inline]
+    pub fn device(&self) -> (u32, u32) {
+        self.device
+    }
+
+    /// The inode of the file that backs this memory area. This is zero if the memory area is not
+    /// backed by a file, or if the current platform does not provide this information. Only Linux
+    /// and FreeBSD provide this information.
+    #[inline]
+    pub fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    /// The kind of the memory area. On platforms other than Linux, this only distinguishes
+    /// between memory areas backed by a file and anonymous memory areas, except for the stack on
+    /// FreeBSD.
+    #[inline]
+    pub fn kind(&self) -> &MemoryAreaKind {
+        &self.kind
+    }
+}
+
+/// The memory usage of a memory area of a process, as reported by `/proc/<pid>/smaps` on Linux.
+/// All sizes are in bytes.
+#[cfg(any(target_os = "android", target_os = "linux"))]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MemoryAreaStats {
+    /// The size of the memory area.
+    pub(crate) size: u64,
+    /// The size of the pages the kernel uses to back the memory area.
+    pub(crate) kernel_page_size: u64,
+    /// The size of the pages the MMU uses to back the memory area.
+    pub(crate) mmu_page_size: u64,
+    /// The amount of memory of the memory area that is currently resident in RAM.
+    pub(crate) rss: u64,
+    /// The proportional share of the memory area that is resident in RAM, where each page is
+    /// divided by the number of processes sharing it.
+    pub(crate) pss: u64,
+    /// The amount of resident memory that is shared with other processes and that has not been
+    /// modified.
+    pub(crate) shared_clean: u64,
+    /// The amount of resident memory that is shared with other processes and that has been
+    /// modified.
+    pub(crate) shared_dirty: u64,
+    /// The amount of resident memory that is private to the process and that has not been modified.
+    pub(crate) private_clean: u64,
+    /// The amount of resident memory that is private to the process and that has been modified.
+    pub(crate) private_dirty: u64,
+    /// The amount of memory that has been marked as referenced or accessed.
+    pub(crate) referenced: u64,
+    /// The amount of memory that does not belong to any file.
+    pub(crate) anonymous: u64,
+    /// The amount of memory that is backed by transparent huge pages.
+    pub(crate) anon_huge_pages: u64,
+    /// The amount of anonymous memory that has been swapped out.
+    pub(crate) swap: u64,
+    /// The proportional share of the swapped out memory, where each page is divided by the number
+    /// of processes sharing it.
+    pub(crate) swap_pss: u64,
+    /// The amount of memory that is locked in RAM.
+    pub(crate) locked: u64,
+    /// The flags associated with the memory area, e.g. `rd` or `wr`.
+    pub(crate) vm_flags: Vec<String>,
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+impl MemoryAreaStats {
+    /// The size of the memory area.
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The size of the pages the kernel uses to back the memory area.
+    #[inline]
+    pub fn kernel_page_size(&self) -> u64 {
+        self.kernel_page_size
+    }
+
+    /// The size of the pages the MMU uses to back the memory area.
+    #[inline]
+    pub fn mmu_page_size(&self) -> u64 {
+        self.mmu_page_size
+    }
+
+    /// The amount of memory of the memory area that is currently resident in RAM.
+    #[inline]
+    pub fn rss(&self) -> u64 {
+        self.rss
+    }
+
+    /// The proportional share of the memory area that is resident in RAM, where each page is
+    /// divided by the number of processes sharing it.
+    #[inline]
+    pub fn pss(&self) -> u64 {
+        self.pss
+    }
+
+    /// The amount of resident memory that is shared with other processes and that has not been
+    /// modified.
+    #[inline]
+    pub fn shared_clean(&self) -> u64 {
+        self.shared_clean
+    }
+
+    /// The amount of resident memory that is shared with other processes and that has been
+    /// modified.
+    #[inline]
+    pub fn shared_dirty(&self) -> u64 {
+        self.shared_dirty
+    }
+
+    /// The amount of resident memory that is private to the process and that has not been modified.
+    #[inline]
+    pub fn private_clean(&self) -> u64 {
+        self.private_clean
+    }
+
+    /// The amount of resident memory that is private to the process and that has been modified.
+    #[inline]
+    pub fn private_dirty(&self) -> u64 {
+        self.private_dirty
+    }
+
+    /// The amount of memory that has been marked as referenced or accessed.
+    #[inline]
+    pub fn referenced(&self) -> u64 {
+        self.referenced
+    }
+
+    /// The amount of memory that does not belong to any file.
+    #[inline]
+    pub fn anonymous(&self) -> u64 {
+        self.anonymous
+    }
+
+    /// The amount of memory that is backed by transparent huge pages.
+    #[inline]
+    pub fn anon_huge_pages(&self) -> u64 {
+        self.anon_huge_pages
+    }
+
+    /// The amount of anonymous memory that has been swapped out.
+    #[inline]
+    pub fn swap(&self) -> u64 {
+        self.swap
+    }
+
+    /// The proportional share of the swapped out memory, where each page is divided by the number
+    /// of processes sharing it.
+    #[inline]
+    pub fn swap_pss(&self) -> u64 {
+        self.swap_pss
+    }
+
+    /// The amount of memory that is locked in RAM.
+    #[inline]
+    pub fn locked(&self) -> u64 {
+        self.locked
+    }
+
+    /// The two-letter codes of the flags associated with the memory area, e.g. `rd` for readable,
+    /// `wr` for writable or `hg` for memory areas that have been advised to use huge pages. See
+    /// `man 5 proc` for the full list of flags.
+    #[inline]
+    pub fn vm_flags(&self) -> &[String] {
+        &self.vm_flags
+    }
+
+    /// Returns `true` if the memory area has the flag with the given two-letter code.
+    pub fn has_vm_flag(&self, flag: &str) -> bool {
+        self.vm_flags.iter().any(|vm_flag| vm_flag == flag)
+    }
 }
 
 /// The memory areas of the process.
@@ -156,6 +366,92 @@ impl MemoryAreas<BufReader<File>> {
 
         Ok(Self { inner })
     }
+
+    /// Creates an iterator over the memory maps for the specified process together with their
+    /// memory usage, as reported by `/proc/<pid>/smaps`. If no process ID is given, then it
+    /// enumerates the memory areas of the current process.
+    ///
+    /// Note that this is considerably slower than [`MemoryAreas::open()`], as the kernel has to
+    /// walk the page tables of every memory area.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn open_detailed(pid: Option<u32>) -> Result<DetailedMemoryAreas<BufReader<File>>, Error> {
+        let inner = platform::DetailedMemoryAreas::open(pid)?;
+
+        Ok(DetailedMemoryAreas { inner })
+    }
+}
+
+impl MemoryAreas<BufReader<File>> {
+    /// Searches the virtual address space of the current process within the given address range
+    /// for free address ranges that can hold a memory mapping of the given size at the given
+    /// alignment, which must be a power of two. Every candidate range starts at an aligned
+    /// address and spans up to the end of the free address range, such that the memory mapping
+    /// can be mapped at its start using [`MmapOptions::with_address()`].
+    ///
+    /// Note that other threads may map memory in the candidate ranges in the meantime.
+    ///
+    /// [`MmapOptions::with_address()`]: crate::MmapOptions::with_address
+    pub fn find_free(
+        size: usize,
+        alignment: usize,
+        range: Range<usize>,
+    ) -> Result<Vec<Range<usize>>, Error> {
+        if size == 0 {
+            return Err(Error::InvalidSize);
+        }
+
+        if !alignment.is_power_of_two() {
+            return Err(Error::InvalidAlignment);
+        }
+
+        let mut candidates = vec![];
+
+        for free in free_ranges(range)? {
+            let Some(start) = free.start.checked_next_multiple_of(alignment) else {
+                continue;
+            };
+
+            if start.checked_add(size).is_some_and(|end| end <= free.end) {
+                candidates.push(start..free.end);
+            }
+        }
+
+        Ok(candidates)
+    }
+}
+
+/// Returns the address ranges within the given address range that are not used by any memory
+/// areas of the current process.
+#[cfg(not(target_os = "windows"))]
+fn free_ranges(range: Range<usize>) -> Result<Vec<Range<usize>>, Error> {
+    let mut areas = MemoryAreas::query_range(range.clone())?
+        .map(|area| area.map(|area| area.range))
+        .collect::<Result<Vec<_>, Error>>()?;
+    areas.sort_by_key(|area| area.start);
+
+    let mut free = vec![];
+    let mut start = range.start;
+
+    for area in areas {
+        if area.start > start {
+            free.push(start..area.start.min(range.end));
+        }
+
+        start = start.max(area.end);
+    }
+
+    if start < range.end {
+        free.push(start..range.end);
+    }
+
+    Ok(free)
+}
+
+/// Returns the address ranges within the given address range that are not used by any memory
+/// areas of the current process. Unlike [`MemoryAreas`], this takes reserved memory into account.
+#[cfg(target_os = "windows")]
+fn free_ranges(range: Range<usize>) -> Result<Vec<Range<usize>>, Error> {
+    platform::free_ranges(range)
 }
 
 impl<B: BufRead> Iterator for MemoryAreas<B> {
@@ -165,3 +461,173 @@ impl<B: BufRead> Iterator for MemoryAreas<B> {
         self.inner.next()
     }
 }
+
+/// The memory areas of the process together with their memory usage. See
+/// [`MemoryAreas::open_detailed()`].
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub struct DetailedMemoryAreas<B> {
+    inner: platform::DetailedMemoryAreas<B>,
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+impl<B> fmt::Debug for DetailedMemoryAreas<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DetailedMemoryAreas")
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+impl<B: BufRead> Iterator for DetailedMemoryAreas<B> {
+    type Item = Result<(MemoryArea, MemoryAreaStats), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// A snapshot of the memory areas of a process, which can be compared against a later snapshot
+/// using [`MemoryMap::diff()`], e.g. to detect address space leaks.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryMap {
+    areas: Vec<MemoryArea>,
+}
+
+impl MemoryMap {
+    /// Takes a snapshot of the memory areas of the specified process. If no process ID is given,
+    /// then it takes a snapshot of the memory areas of the current process.
+    pub fn snapshot(pid: Option<u32>) -> Result<Self, Error> {
+        MemoryAreas::open(pid)?.collect()
+    }
+
+    /// The memory areas in the snapshot.
+    #[inline]
+    pub fn areas(&self) -> &[MemoryArea] {
+        &self.areas
+    }
+
+    /// The total size of the memory areas in bytes.
+    pub fn total_size(&self) -> usize {
+        self.areas.iter().map(|area| area.range.len()).sum()
+    }
+
+    /// The total size of the memory areas in bytes for every protection.
+    pub fn size_by_protection(&self) -> HashMap<Protection, usize> {
+        let mut sizes = HashMap::new();
+
+        for area in &self.areas {
+            *sizes.entry(area.protection).or_default() += area.range.len();
+        }
+
+        sizes
+    }
+
+    /// The total size of the memory areas in bytes for every backing file, where anonymous memory
+    /// is accounted for as `None`.
+    pub fn size_by_path(&self) -> HashMap<Option<PathBuf>, usize> {
+        let mut sizes = HashMap::new();
+
+        for area in &self.areas {
+            *sizes.entry(area.path().cloned()).or_default() += area.range.len();
+        }
+
+        sizes
+    }
+
+    /// Compares this snapshot against a later snapshot. Memory areas are matched by their start
+    /// address, where a matching memory area is considered changed if its size, protection or
+    /// backing differ.
+    pub fn diff(&self, later: &MemoryMap) -> MemoryMapDiff {
+        let earlier: HashMap<usize, &MemoryArea> =
+            self.areas.iter().map(|area| (area.start(), area)).collect();
+        let later_by_start: HashMap<usize, &MemoryArea> = later
+            .areas
+            .iter()
+            .map(|area| (area.start(), area))
+            .collect();
+
+        let mut diff = MemoryMapDiff::default();
+
+        for area in &self.areas {
+            match later_by_start.get(&area.start()) {
+                Some(new) if !same_memory_area(area, new) => {
+                    diff.changed.push((area.clone(), (*new).clone()))
+                }
+                Some(_) => (),
+                None => diff.removed.push(area.clone()),
+            }
+        }
+
+        diff.added = later
+            .areas
+            .iter()
+            .filter(|area| !earlier.contains_key(&area.start()))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+fn same_memory_area(a: &MemoryArea, b: &MemoryArea) -> bool {
+    a.range == b.range
+        && a.protection == b.protection
+        && a.share_mode == b.share_mode
+        && a.path == b.path
+        && a.kind == b.kind
+}
+
+impl FromIterator<MemoryArea> for MemoryMap {
+    fn from_iter<I: IntoIterator<Item = MemoryArea>>(iter: I) -> Self {
+        Self {
+            areas: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// The differences between two snapshots of the memory areas of a process. See
+/// [`MemoryMap::diff()`].
+#[derive(Clone, Debug, Default)]
+pub struct MemoryMapDiff {
+    added: Vec<MemoryArea>,
+    removed: Vec<MemoryArea>,
+    changed: Vec<(MemoryArea, MemoryArea)>,
+}
+
+impl MemoryMapDiff {
+    /// The memory areas that have been added in the later snapshot.
+    #[inline]
+    pub fn added(&self) -> &[MemoryArea] {
+        &self.added
+    }
+
+    /// The memory areas that have been removed in the later snapshot.
+    #[inline]
+    pub fn removed(&self) -> &[MemoryArea] {
+        &self.removed
+    }
+
+    /// The memory areas that have changed, as pairs of the earlier and the later memory area.
+    #[inline]
+    pub fn changed(&self) -> &[(MemoryArea, MemoryArea)] {
+        &self.changed
+    }
+
+    /// Returns `true` if the snapshots are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// The change in the total size of the memory areas in bytes, which is positive if the
+    /// address space has grown.
+    pub fn size_delta(&self) -> isize {
+        let added = self.added.iter().map(|area| area.range.len() as isize);
+        let removed = self.removed.iter().map(|area| area.range.len() as isize);
+        let changed = self
+            .changed
+            .iter()
+            .map(|(old, new)| new.range.len() as isize - old.range.len() as isize);
+
+        added.sum::<isize>() - removed.sum::<isize>() + changed.sum::<isize>()
+    }
+}
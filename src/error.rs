@@ -1,6 +1,6 @@
 //! This module implements the error type used throughout this crate.
 
-use crate::UnsafeMmapFlags;
+use crate::{Advice, UnsafeMmapFlags};
 use thiserror::Error;
 
 /// The error type.
@@ -10,6 +10,10 @@ pub enum Error {
     #[error("{0:?} must be set")]
     UnsafeFlagNeeded(UnsafeMmapFlags),
 
+    /// The advice is not supported on the current platform.
+    #[error("{0:?} is not supported on this platform")]
+    UnsupportedAdvice(Advice),
+
     /// The size is invalid.
     #[error("invalid size")]
     InvalidSize,
@@ -18,6 +22,10 @@ pub enum Error {
     #[error("invalid offset")]
     InvalidOffset,
 
+    /// The address is not suitably aligned.
+    #[error("invalid alignment")]
+    InvalidAlignment,
+
     /// The operation is invalid.
     #[error("invalid operation")]
     InvalidOperation,
@@ -34,6 +42,18 @@ pub enum Error {
     #[error("the memory maps must share the same backing")]
     BackingMismatch,
 
+    /// The address range is already in use by another memory mapping.
+    #[error("the address range is already in use")]
+    AddressInUse,
+
+    /// The memory map has been sealed and can no longer be changed.
+    #[error("the memory map has been sealed")]
+    Sealed,
+
+    /// The kernel does not track soft-dirty pages.
+    #[error("soft-dirty pages are not supported by the kernel")]
+    SoftDirtyUnsupported,
+
     /// Represents [`std::io::Error`].
     #[error(transparent)]
     Io(#[from] std::io::Error),
@@ -70,3 +90,15 @@ pub enum Error {
     #[error(transparent)]
     Windows(#[from] windows::core::Error),
 }
+
+#[cfg(feature = "bytemuck")]
+impl From<bytemuck::PodCastError> for Error {
+    fn from(e: bytemuck::PodCastError) -> Self {
+        match e {
+            bytemuck::PodCastError::TargetAlignmentGreaterAndInputNotAligned
+            | bytemuck::PodCastError::AlignmentMismatch => Self::InvalidAlignment,
+            bytemuck::PodCastError::OutputSliceWouldHaveSlop
+            | bytemuck::PodCastError::SizeMismatch => Self::InvalidSize,
+        }
+    }
+}
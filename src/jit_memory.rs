@@ -0,0 +1,101 @@
+//! This module implements dual-mapped memory for JIT engines, where the same pages are mapped once
+//! as writable and once as executable.
+
+use crate::error::Error;
+use crate::mmap::{Mmap, MmapFlags, MmapMut, MmapOptions, anonymous_shared_memory};
+
+/// Memory for JIT engines that maps the same pages twice: once as writable and once as executable,
+/// at different addresses. This allows code to be emitted incrementally through the writable view
+/// while previously emitted code keeps running from the executable view, without the pages ever
+/// being both writable and executable at the same address (W^X), and without changing the
+/// protection of the pages using [`MmapMut::make_exec()`] and [`Mmap::make_mut()`].
+///
+/// The pages are backed by anonymous shared memory, i.e. `memfd_create()` on Linux and Android
+/// and an unlinked POSIX shared memory object on other platforms. After writing code, call
+/// [`JitMemory::commit()`] before executing it to flush the instruction cache.
+///
+/// Note that this requires the operating system to allow executable mappings of shared memory,
+/// which may not be the case on hardened systems, e.g. with SELinux denying `execmem`, or on macOS
+/// with the hardened runtime enabled.
+#[derive(Debug)]
+pub struct JitMemory {
+    writable: MmapMut,
+    executable: Mmap,
+}
+
+impl JitMemory {
+    /// Creates new JIT memory of the given size in bytes.
+    pub fn new(size: usize) -> Result<Self, Error> {
+        let file = anonymous_shared_memory("mmap-rs-jit", size)?;
+
+        // The writable view is only handed out through `&mut self`, while the executable view is
+        // only handed out through `&self`, such that neither can be observed while the other is
+        // being modified.
+        let writable = unsafe {
+            MmapOptions::new(size)?
+                .with_file(&file, 0)
+                .with_flags(MmapFlags::SHARED)
+                .map_mut()?
+        };
+
+        let executable = unsafe {
+            MmapOptions::new(size)?
+                .with_file(&file, 0)
+                .with_flags(MmapFlags::SHARED)
+                .map_exec()?
+        };
+
+        Ok(Self {
+            writable,
+            executable,
+        })
+    }
+
+    /// Returns the size of the JIT memory in bytes.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.executable.size()
+    }
+
+    /// Returns the writable view of the JIT memory.
+    #[inline]
+    pub fn writable(&self) -> &MmapMut {
+        &self.writable
+    }
+
+    /// Extracts a mutable slice of the writable view of the JIT memory to emit code into.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.writable.as_mut_slice()
+    }
+
+    /// Returns the executable view of the JIT memory.
+    #[inline]
+    pub fn executable(&self) -> &Mmap {
+        &self.executable
+    }
+
+    /// Returns a raw pointer to the executable view of the JIT memory, i.e. the address at which
+    /// the emitted code should be executed.
+    #[inline]
+    pub fn as_exec_ptr(&self) -> *const u8 {
+        self.executable.as_ptr()
+    }
+
+    /// Converts an offset into the JIT memory to the corresponding address in the executable view.
+    /// Returns [`Error::InvalidOffset`] if the offset is out of bounds.
+    pub fn exec_address(&self, offset: usize) -> Result<usize, Error> {
+        if offset >= self.size() {
+            return Err(Error::InvalidOffset);
+        }
+
+        Ok(self.executable.start() + offset)
+    }
+
+    /// Commits the code that has been written to the writable view, such that it can be executed
+    /// from the executable view. This flushes the instruction cache of the executable view on
+    /// architectures where this is required, see [`Mmap::flush_icache()`].
+    pub fn commit(&self) -> Result<(), Error> {
+        self.executable.flush_icache()
+    }
+}
@@ -7,15 +7,55 @@
 
 mod areas;
 pub mod error;
+#[cfg(unix)]
+mod jit_memory;
 mod mmap;
+#[cfg(all(unix, not(target_os = "android")))]
+mod named_shared_memory;
 mod os_impl;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod page_map;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod process_memory;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod protection_key;
+#[cfg(unix)]
+mod ring_mapping;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod shared_memory;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod userfaultfd;
 
 pub use areas::*;
 pub use error::Error;
+#[cfg(unix)]
+pub use jit_memory::*;
 pub use mmap::*;
+#[cfg(all(unix, not(target_os = "android")))]
+pub use named_shared_memory::*;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use page_map::*;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use process_memory::*;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use protection_key::{ProtectionKey, ProtectionKeyRights};
+#[cfg(unix)]
+pub use ring_mapping::*;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use shared_memory::*;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use userfaultfd::*;
 
 #[cfg(test)]
 mod tests {
+    use crate::MmapOptions;
+
+    /// Returns the options for a memory mapping of the given size surrounded by guard pages, which
+    /// prevent it from being merged with other memory mappings into a single memory area.
+    fn isolated(size: usize) -> MmapOptions<'static> {
+        MmapOptions::new(size).unwrap().with_guard_pages(1, 1)
+    }
+
     #[test]
     fn reserve_none() {
         use crate::{MemoryAreas, MmapNone, MmapOptions, Protection, ShareMode};
@@ -25,11 +65,11 @@ mod tests {
             .reserve_none()
             .unwrap();
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
 
         let mapping: MmapNone = mapping.try_into().unwrap();
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
 
         let region = MemoryAreas::query(mapping.as_ptr() as usize)
             .unwrap()
@@ -42,6 +82,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_conversion)]
     fn reserve() {
         use crate::{MemoryAreas, Mmap, MmapOptions, Protection, ShareMode};
 
@@ -50,11 +91,11 @@ mod tests {
             .map()
             .unwrap();
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
 
         let mapping: Mmap = mapping.try_into().unwrap();
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
 
         let region = MemoryAreas::query(mapping.as_ptr() as usize)
             .unwrap()
@@ -75,13 +116,13 @@ mod tests {
             .reserve_mut()
             .unwrap();
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
 
         let mut mapping: MmapMut = mapping.try_into().unwrap();
 
         mapping[0] = 0x42;
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
         assert_eq!(mapping[0], 0x42);
 
         let region = MemoryAreas::query(mapping.as_ptr() as usize)
@@ -103,7 +144,7 @@ mod tests {
             .map_none()
             .unwrap();
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
 
         let region = MemoryAreas::query(mapping.as_ptr() as usize)
             .unwrap()
@@ -124,7 +165,7 @@ mod tests {
             .map()
             .unwrap();
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
 
         let region = MemoryAreas::query(mapping.as_ptr() as usize)
             .unwrap()
@@ -147,7 +188,7 @@ mod tests {
 
         mapping[0] = 0x42;
 
-        assert!(mapping.as_ptr() != std::ptr::null());
+        assert!(!mapping.as_ptr().is_null());
         assert_eq!(mapping[0], 0x42);
 
         let region = MemoryAreas::query(mapping.as_ptr() as usize)
@@ -170,7 +211,7 @@ mod tests {
 
         let mut bytes = vec![0u8; MmapOptions::page_size()];
         bytes[0] = 0x42;
-        file.as_file_mut().write(&bytes).unwrap();
+        file.as_file_mut().write_all(&bytes).unwrap();
 
         let mapping = unsafe {
             MmapOptions::new(MmapOptions::page_size())
@@ -216,7 +257,7 @@ mod tests {
         let mut file = NamedTempFile::new().unwrap();
 
         let mut bytes = vec![0u8; MmapOptions::page_size()];
-        file.as_file_mut().write(&bytes).unwrap();
+        file.as_file_mut().write_all(&bytes).unwrap();
 
         let mut mapping = unsafe {
             MmapOptions::new(MmapOptions::page_size())
@@ -255,7 +296,7 @@ mod tests {
         assert!(region.path().is_some());
 
         file.as_file_mut().seek(SeekFrom::Start(0)).unwrap();
-        file.as_file().read(&mut bytes).unwrap();
+        file.as_file().read_exact(&mut bytes).unwrap();
         assert_eq!(bytes[0], 0x42);
     }
 
@@ -268,7 +309,7 @@ mod tests {
         let mut file = NamedTempFile::new().unwrap();
 
         let mut bytes = vec![0u8; MmapOptions::page_size()];
-        file.as_file_mut().write(&bytes).unwrap();
+        file.as_file_mut().write_all(&bytes).unwrap();
 
         let mut mapping = unsafe {
             MmapOptions::new(MmapOptions::page_size())
@@ -305,7 +346,7 @@ mod tests {
         assert!(region.path().is_some());
 
         file.as_file_mut().seek(SeekFrom::Start(0)).unwrap();
-        file.as_file().read(&mut bytes).unwrap();
+        file.as_file().read_exact(&mut bytes).unwrap();
         assert_ne!(bytes[0], 0x42);
     }
 
@@ -389,7 +430,7 @@ mod tests {
         let mut bytes = vec![0u8; 2 * MmapOptions::page_size()];
         bytes[0] = 0x1;
         bytes[MmapOptions::page_size()] = 0x2;
-        file.as_file_mut().write(&bytes).unwrap();
+        file.as_file_mut().write_all(&bytes).unwrap();
 
         let mut mapping = unsafe {
             MmapOptions::new(2 * MmapOptions::page_size())
@@ -508,4 +549,1205 @@ mod tests {
         assert_eq!(region.start(), right.as_ptr() as usize);
         assert!(areas.next().is_none());
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn resize() {
+        use crate::{Error, MmapOptions, RemapPolicy};
+
+        let mut mapping = MmapOptions::new(MmapOptions::page_size())
+            .unwrap()
+            .map_mut()
+            .unwrap();
+
+        mapping[0] = 0x42;
+
+        // Growing the mapping should preserve its contents.
+        mapping
+            .resize(4 * MmapOptions::page_size(), RemapPolicy::MayMove)
+            .unwrap();
+
+        assert_eq!(mapping.len(), 4 * MmapOptions::page_size());
+        assert_eq!(mapping[0], 0x42);
+        mapping[3 * MmapOptions::page_size()] = 0x43;
+
+        // Shrinking the mapping in place should keep the start address.
+        let start = mapping.start();
+
+        mapping
+            .resize(MmapOptions::page_size(), RemapPolicy::InPlaceOnly)
+            .unwrap();
+
+        assert_eq!(mapping.start(), start);
+        assert_eq!(mapping.len(), MmapOptions::page_size());
+        assert_eq!(mapping[0], 0x42);
+
+        // Growing a file mapping could expose pages beyond the end of the file.
+        let file = tempfile::tempfile().unwrap();
+        file.set_len(2 * MmapOptions::page_size() as u64).unwrap();
+
+        let mut mapping = unsafe {
+            MmapOptions::new(2 * MmapOptions::page_size())
+                .unwrap()
+                .with_file(&file, 0)
+                .map_mut()
+                .unwrap()
+        };
+
+        assert!(matches!(
+            mapping.resize(4 * MmapOptions::page_size(), RemapPolicy::MayMove),
+            Err(Error::InvalidOperation)
+        ));
+
+        mapping
+            .resize(MmapOptions::page_size(), RemapPolicy::InPlaceOnly)
+            .unwrap();
+        assert_eq!(mapping.len(), MmapOptions::page_size());
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn typed_views() {
+        use crate::{Error, MmapOptions};
+
+        let mut mapping = MmapOptions::new(MmapOptions::page_size())
+            .unwrap()
+            .map_mut()
+            .unwrap();
+
+        mapping
+            .as_typed_slice_mut::<u32>(0..16)
+            .unwrap()
+            .copy_from_slice(&[1, 2, 3, 4]);
+
+        assert_eq!(mapping[..4], 1u32.to_ne_bytes());
+        assert_eq!(*mapping.as_typed::<u32>().unwrap(), 1);
+
+        *mapping.as_typed_mut::<u64>().unwrap() = 0x42;
+
+        assert_eq!(mapping[..8], 0x42u64.to_ne_bytes());
+        assert_eq!(*mapping.as_typed::<u64>().unwrap(), 0x42);
+        assert_eq!(mapping.as_typed_slice::<u32>(8..16).unwrap(), &[3, 4]);
+
+        assert!(matches!(
+            mapping.as_typed_slice::<u32>(1..9),
+            Err(Error::InvalidAlignment)
+        ));
+        assert!(matches!(
+            mapping.as_typed_slice::<u32>(0..6),
+            Err(Error::InvalidSize)
+        ));
+        assert!(matches!(
+            mapping.as_typed_slice::<u32>(0..MmapOptions::page_size() + 4),
+            Err(Error::InvalidOffset)
+        ));
+    }
+
+    #[test]
+    fn advise() {
+        use crate::{Advice, MmapOptions};
+
+        let mut mapping = MmapOptions::new(2 * MmapOptions::page_size())
+            .unwrap()
+            .map_mut()
+            .unwrap();
+
+        mapping[0] = 0x42;
+
+        mapping
+            .advise(0..MmapOptions::page_size(), Advice::WillNeed)
+            .unwrap();
+        assert_eq!(mapping[0], 0x42);
+
+        // The range must start at a page boundary and must be within bounds.
+        assert!(
+            mapping
+                .advise(1..MmapOptions::page_size(), Advice::WillNeed)
+                .is_err()
+        );
+        assert!(
+            mapping
+                .advise(0..3 * MmapOptions::page_size(), Advice::WillNeed)
+                .is_err()
+        );
+
+        // Private anonymous pages are zero-filled after MADV_DONTNEED on Linux.
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        {
+            mapping
+                .advise(0..MmapOptions::page_size(), Advice::DontNeed)
+                .unwrap();
+            assert_eq!(mapping[0], 0);
+        }
+    }
+
+    #[test]
+    fn protect_range() {
+        use crate::{Error, MemoryAreas, MmapOptions, Protection};
+
+        let page_size = MmapOptions::page_size();
+
+        let mut mapping = MmapOptions::new(3 * page_size).unwrap().map_mut().unwrap();
+
+        // Mutable memory maps must remain readable and writable.
+        assert!(matches!(
+            mapping.protect_range(page_size..2 * page_size, Protection::empty()),
+            Err(Error::InvalidOperation)
+        ));
+        assert!(matches!(
+            mapping.protect_range(page_size..2 * page_size, Protection::READ),
+            Err(Error::InvalidOperation)
+        ));
+        assert!(matches!(
+            mapping.protect_range(1..page_size, Protection::READ | Protection::WRITE),
+            Err(Error::InvalidOffset)
+        ));
+        assert!(matches!(
+            mapping.protect_range(
+                0..page_size,
+                Protection::READ | Protection::WRITE | Protection::EXECUTE
+            ),
+            Err(Error::UnsafeFlagNeeded(_))
+        ));
+
+        mapping[0] = 0x42;
+        mapping[2 * page_size] = 0x43;
+
+        let mut mapping = mapping.make_read_only().unwrap();
+
+        // Immutable memory maps must remain readable.
+        assert!(matches!(
+            mapping.protect_range(page_size..2 * page_size, Protection::empty()),
+            Err(Error::InvalidOperation)
+        ));
+
+        // Make the middle page executable.
+        mapping
+            .protect_range(
+                page_size..2 * page_size,
+                Protection::READ | Protection::EXECUTE,
+            )
+            .unwrap();
+
+        let region = MemoryAreas::query(mapping.start() + page_size)
+            .unwrap()
+            .unwrap();
+        assert!(region.protection().contains(Protection::EXECUTE));
+
+        // Splitting and merging should carry over the protection of each page.
+        let mut rest = mapping.split_off(page_size).unwrap();
+        let last = rest.split_off(page_size).unwrap();
+
+        assert_eq!(
+            rest.protection_at(0).unwrap(),
+            Protection::READ | Protection::EXECUTE
+        );
+        assert_eq!(last.protection_at(0).unwrap(), Protection::READ);
+
+        rest.merge(last).unwrap();
+        mapping.merge(rest).unwrap();
+
+        assert_eq!(mapping.protection_at(0).unwrap(), Protection::READ);
+        assert_eq!(
+            mapping.protection_at(page_size).unwrap(),
+            Protection::READ | Protection::EXECUTE
+        );
+        assert_eq!(
+            mapping.protection_at(2 * page_size).unwrap(),
+            Protection::READ
+        );
+        assert_eq!(mapping[2 * page_size], 0x43);
+
+        // Remapping the whole mapping resets the protection of all pages.
+        let mapping = mapping.make_mut().unwrap();
+
+        assert_eq!(
+            mapping.protection_at(page_size).unwrap(),
+            Protection::READ | Protection::WRITE
+        );
+        assert_eq!(mapping[0], 0x42);
+        assert_eq!(mapping[page_size], 0);
+    }
+
+    #[test]
+    fn guard_pages() {
+        use crate::{MemoryAreas, MmapOptions, Protection};
+
+        let page_size = MmapOptions::page_size();
+
+        let mut mapping = MmapOptions::new(2 * page_size)
+            .unwrap()
+            .with_guard_pages(1, 2)
+            .map_mut()
+            .unwrap();
+
+        assert_eq!(mapping.len(), 2 * page_size);
+        mapping[0] = 0x42;
+        mapping[2 * page_size - 1] = 0x43;
+
+        let region = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+        assert!(
+            region
+                .protection()
+                .contains(Protection::READ | Protection::WRITE)
+        );
+
+        // The guard pages should be inaccessible. Microsoft Windows does not report reserved
+        // memory as memory areas.
+        #[cfg(unix)]
+        {
+            let before = MemoryAreas::query(mapping.start() - page_size)
+                .unwrap()
+                .unwrap();
+            let after = MemoryAreas::query(mapping.end() + page_size)
+                .unwrap()
+                .unwrap();
+
+            assert!(before.protection().is_empty());
+            assert!(after.protection().is_empty());
+        }
+
+        // Mappings with guard pages cannot be resized.
+        assert!(
+            mapping
+                .resize(page_size, crate::RemapPolicy::MayMove)
+                .is_err()
+        );
+
+        // Splitting should keep the guard pages around both halves.
+        let rest = mapping.split_off(page_size).unwrap();
+
+        assert_eq!(mapping[0], 0x42);
+        assert_eq!(rest[page_size - 1], 0x43);
+        assert_eq!(mapping.end(), rest.start());
+
+        mapping.merge(rest).unwrap();
+
+        // A memory mapping that has been placed over the guard pages cannot be merged, as the
+        // guard pages would end up inside the merged memory mapping.
+        #[cfg(unix)]
+        {
+            use crate::{Error, UnsafeMmapFlags};
+
+            let other = unsafe {
+                MmapOptions::new(page_size)
+                    .unwrap()
+                    .with_address(mapping.end())
+                    .with_unsafe_flags(UnsafeMmapFlags::MAP_FIXED)
+                    .map_mut()
+                    .unwrap()
+            };
+
+            match mapping.merge(other) {
+                // The guard pages of the memory mapping still cover the other memory mapping.
+                Err((Error::MustBeAdjacent, other)) => std::mem::forget(other),
+                _ => panic!("merged a memory mapping over guard pages"),
+            }
+        }
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn shared_memory() {
+        use crate::{MmapOptions, Seals, SharedMemory, SharedMemoryFlags};
+
+        let page_size = MmapOptions::page_size();
+
+        let shared_memory = SharedMemory::new(
+            "mmap-rs",
+            page_size,
+            SharedMemoryFlags::CLOEXEC | SharedMemoryFlags::ALLOW_SEALING,
+        )
+        .unwrap();
+
+        assert_eq!(shared_memory.len().unwrap(), page_size as u64);
+
+        let mut writer = unsafe {
+            MmapOptions::new(page_size)
+                .unwrap()
+                .with_shared_memory(&shared_memory, 0)
+        }
+        .map_mut()
+        .unwrap();
+
+        writer[0] = 0x42;
+
+        // The write seal cannot be added while there is a shared writable mapping.
+        assert!(shared_memory.add_seals(Seals::WRITE).is_err());
+        drop(writer);
+
+        shared_memory
+            .add_seals(Seals::SHRINK | Seals::GROW | Seals::WRITE)
+            .unwrap();
+
+        assert_eq!(
+            shared_memory.seals().unwrap(),
+            Seals::SHRINK | Seals::GROW | Seals::WRITE
+        );
+        assert!(shared_memory.set_len(2 * page_size as u64).is_err());
+
+        let reader = unsafe {
+            MmapOptions::new(page_size)
+                .unwrap()
+                .with_shared_memory(&shared_memory, 0)
+        }
+        .map()
+        .unwrap();
+
+        assert_eq!(reader[0], 0x42);
+
+        let result = unsafe {
+            MmapOptions::new(page_size)
+                .unwrap()
+                .with_shared_memory(&shared_memory, 0)
+        }
+        .map_mut();
+
+        assert!(result.is_err());
+        assert_eq!(reader[0], 0x42);
+    }
+
+    #[cfg(all(unix, not(target_os = "android")))]
+    #[test]
+    fn named_shared_memory() {
+        use crate::{MemoryAreas, MmapOptions, NamedSharedMemory, ShareMode};
+        use nix::sys::wait::{WaitStatus, waitpid};
+        use nix::unistd::{ForkResult, fork};
+
+        let page_size = MmapOptions::page_size();
+        let name = format!("/mmap-rs-{}", std::process::id());
+
+        let shared_memory = NamedSharedMemory::create(&name, page_size).unwrap();
+
+        let mut mapping = unsafe {
+            MmapOptions::new(page_size)
+                .unwrap()
+                .with_shared_memory(&shared_memory, 0)
+        }
+        .map_mut()
+        .unwrap();
+
+        let region = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+        assert_eq!(region.share_mode(), ShareMode::Shared);
+
+        // The child process only writes to the memory mapping, as anything else is not safe to do
+        // after forking a multi-threaded process.
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                mapping[0] = 0x42;
+                unsafe { libc::_exit(0) };
+            }
+            ForkResult::Parent { child } => {
+                assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Exited(child, 0));
+            }
+        }
+
+        assert_eq!(mapping[0], 0x42);
+
+        let other = NamedSharedMemory::open_read_only(&name).unwrap();
+        NamedSharedMemory::unlink(&name).unwrap();
+
+        let reader = unsafe {
+            MmapOptions::new(page_size)
+                .unwrap()
+                .with_shared_memory(&other, 0)
+        }
+        .map()
+        .unwrap();
+
+        assert_eq!(reader[0], 0x42);
+        assert!(NamedSharedMemory::open(&name).is_err());
+    }
+
+    #[test]
+    fn owned_file() {
+        use crate::{MmapFlags, MmapOptions};
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        let page_size = MmapOptions::page_size();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&vec![0u8; 2 * page_size]).unwrap();
+
+        // The memory mapping takes ownership of the file.
+        let mut mapping = unsafe {
+            MmapOptions::new(2 * page_size)
+                .unwrap()
+                .with_flags(MmapFlags::SHARED)
+                .with_owned_file(file, 0)
+        }
+        .map_mut()
+        .unwrap();
+
+        mapping[0] = 0x42;
+        mapping[page_size] = 0x43;
+        mapping.flush(0..2 * page_size).unwrap();
+
+        // Both halves keep the file alive.
+        let rest = mapping.split_off(page_size).unwrap();
+        assert!(rest.file().is_some());
+        mapping.merge(rest).unwrap();
+
+        let mut file = mapping.file().unwrap();
+        let mut bytes = vec![0u8; 2 * page_size];
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_exact(&mut bytes).unwrap();
+
+        assert_eq!(bytes[0], 0x42);
+        assert_eq!(bytes[page_size], 0x43);
+
+        // Anonymous memory mappings do not have a file.
+        let mapping = MmapOptions::new(page_size).unwrap().map().unwrap();
+        assert!(mapping.file().is_none());
+    }
+
+    #[test]
+    fn open_file() {
+        use crate::{Error, Mmap, MmapOptions};
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let page_size = MmapOptions::page_size();
+        let mut file = NamedTempFile::new().unwrap();
+
+        // An empty file cannot be mapped.
+        assert!(matches!(
+            unsafe { Mmap::open_file(file.as_file(), ..) },
+            Err(Error::InvalidSize)
+        ));
+
+        let bytes: Vec<u8> = (0..3 * page_size + 100).map(|i| (i % 251) as u8).collect();
+        file.as_file_mut().write_all(&bytes).unwrap();
+
+        // The range does not have to be page aligned.
+        let mapping =
+            unsafe { Mmap::open_file(file.as_file(), 100..(2 * page_size + 5) as u64) }.unwrap();
+        assert_eq!(mapping.as_slice(), &bytes[100..2 * page_size + 5]);
+        assert_eq!(mapping.as_ptr() as usize % page_size, 100);
+
+        // The size is derived from the file.
+        let mapping =
+            unsafe { Mmap::open_path(file.path(), (2 * page_size + 1) as u64..) }.unwrap();
+        assert_eq!(mapping.as_slice(), &bytes[2 * page_size + 1..]);
+        assert!(mapping.file().is_some());
+
+        // The range cannot extend beyond the end of the file.
+        assert!(matches!(
+            unsafe { Mmap::open_file(file.as_file(), ..=bytes.len() as u64) },
+            Err(Error::InvalidSize)
+        ));
+    }
+
+    #[test]
+    fn unaligned_file_offset() {
+        use crate::{MmapFlags, MmapOptions};
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
+        let page_size = MmapOptions::page_size();
+        let granularity = MmapOptions::allocation_granularity();
+        let mut file = NamedTempFile::new().unwrap();
+
+        let bytes: Vec<u8> = (0..2 * granularity + page_size)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        file.as_file_mut().write_all(&bytes).unwrap();
+
+        let offset = granularity + 100;
+        let size = granularity + page_size - 100;
+
+        let mut mapping = unsafe {
+            MmapOptions::new(size)
+                .unwrap()
+                .with_flags(MmapFlags::SHARED)
+                .with_file(file.as_file(), offset as u64)
+                .map_mut()
+                .unwrap()
+        };
+
+        assert_eq!(mapping.len(), size);
+        assert_eq!(mapping.as_slice(), &bytes[offset..]);
+
+        mapping[0] = 0x42;
+        mapping.flush(0..size).unwrap();
+
+        // The memory mapping can only be split at page boundaries of the underlying mapping.
+        assert!(mapping.split_off(page_size).is_err());
+
+        let rest = mapping.split_off(page_size - 100).unwrap();
+        assert_eq!(rest.as_ptr() as usize % page_size, 0);
+        assert_eq!(rest.as_slice(), &bytes[granularity + page_size..]);
+
+        mapping.merge(rest).unwrap();
+        assert_eq!(mapping.len(), size);
+
+        let left = mapping.split_to(page_size - 100).unwrap();
+        assert_eq!(left[0], 0x42);
+        assert_eq!(mapping.as_slice(), &bytes[granularity + page_size..]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn memory_area_kind() {
+        use crate::{MemoryAreaKind, MemoryAreas, MmapOptions};
+        use std::io::Write;
+        use std::os::unix::fs::MetadataExt;
+        use tempfile::NamedTempFile;
+
+        let page_size = MmapOptions::page_size();
+        let mut file = NamedTempFile::new().unwrap();
+        file.as_file_mut().write_all(&vec![0u8; page_size]).unwrap();
+
+        let mapping = unsafe {
+            MmapOptions::new(page_size)
+                .unwrap()
+                .with_file(file.as_file(), 0)
+                .map()
+                .unwrap()
+        };
+
+        let metadata = file.as_file().metadata().unwrap();
+        let area = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+
+        assert_eq!(area.kind(), &MemoryAreaKind::File);
+        assert_eq!(area.inode(), metadata.ino());
+        assert_eq!(
+            area.device(),
+            (libc::major(metadata.dev()), libc::minor(metadata.dev()))
+        );
+
+        // The path should no longer be listed once the file has been deleted.
+        let path = file.path().to_path_buf();
+        drop(file);
+
+        let area = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+        assert_eq!(area.kind(), &MemoryAreaKind::Deleted);
+        assert_eq!(area.path(), Some(&path));
+
+        let mapping = MmapOptions::new(page_size).unwrap().map().unwrap();
+        let area = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+        assert_eq!(area.kind(), &MemoryAreaKind::Anonymous);
+        assert_eq!(area.inode(), 0);
+
+        let vdso = unsafe { libc::getauxval(libc::AT_SYSINFO_EHDR) } as usize;
+
+        if vdso != 0 {
+            let area = MemoryAreas::query(vdso).unwrap().unwrap();
+            assert_eq!(area.kind(), &MemoryAreaKind::Vdso);
+            assert!(area.path().is_none());
+        }
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn memory_area_stats() {
+        use crate::{MemoryAreas, MmapOptions};
+
+        let page_size = MmapOptions::page_size();
+        let mut mapping = MmapOptions::new(4 * page_size).unwrap().map_mut().unwrap();
+
+        // Touch two of the pages, such that they become resident.
+        mapping[0] = 0x42;
+        mapping[2 * page_size] = 0x42;
+
+        let (area, stats) = MemoryAreas::open_detailed(None)
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .find(|(area, _)| area.range().contains(&mapping.start()))
+            .unwrap();
+
+        assert!(area.end() >= mapping.end());
+        assert!(stats.size() >= 4 * page_size as u64);
+        assert!(stats.rss() >= 2 * page_size as u64);
+        assert!(stats.private_dirty() >= 2 * page_size as u64);
+        assert_eq!(stats.kernel_page_size(), page_size as u64);
+        assert!(stats.has_vm_flag("rd"));
+        assert!(stats.has_vm_flag("wr"));
+    }
+
+    #[test]
+    fn residency() {
+        use crate::MmapOptions;
+
+        let page_size = MmapOptions::page_size();
+        let mut mapping = MmapOptions::new(4 * page_size).unwrap().map_mut().unwrap();
+
+        mapping[0] = 0x42;
+        mapping[2 * page_size] = 0x42;
+
+        let residency = mapping.residency(0..4 * page_size).unwrap();
+
+        assert_eq!(residency.len(), 4);
+        assert!(residency.is_resident(0));
+        assert!(residency.is_resident(2));
+        assert!(residency.resident_fraction() >= 0.5);
+
+        // The range does not have to be page aligned.
+        let residency = mapping.residency(page_size + 1..2 * page_size + 1).unwrap();
+        assert_eq!(residency.len(), 2);
+        assert!(residency.is_resident(1));
+
+        assert!(mapping.residency(0..5 * page_size).is_err());
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn page_map() {
+        use crate::{MmapOptions, PageMap};
+
+        let page_size = MmapOptions::page_size();
+        let mut mapping = MmapOptions::new(2 * page_size).unwrap().map_mut().unwrap();
+
+        mapping[0] = 0x42;
+
+        let page_map = PageMap::open(None).unwrap();
+        let pages = page_map.query_mapping(&mapping).unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].address(), mapping.start());
+        assert!(pages[0].present());
+        assert!(!pages[0].swapped());
+        assert!(!pages[0].file_or_shared());
+        assert!(pages[0].swap_entry().is_none());
+
+        // The page that has not been touched yet should not be present.
+        assert!(!pages[1].present());
+        assert!(pages[1].pfn().is_none());
+
+        let page = page_map.query(mapping.start() + 1).unwrap();
+        assert_eq!(page.address(), mapping.start());
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn dirty_pages() {
+        use crate::MmapOptions;
+
+        let page_size = MmapOptions::page_size();
+
+        // Merging the memory mapping with other memory mappings would mark all pages as
+        // soft-dirty again.
+        let mut mapping = isolated(4 * page_size).map_mut().unwrap();
+
+        mapping[0] = 1;
+
+        mapping.clear_soft_dirty().unwrap();
+
+        mapping[page_size] = 2;
+        mapping[2 * page_size] = 3;
+
+        assert_eq!(
+            mapping.dirty_pages(0..mapping.size()).unwrap(),
+            vec![page_size..3 * page_size]
+        );
+        assert_eq!(
+            mapping.dirty_pages(page_size + 1..page_size + 2).unwrap(),
+            vec![page_size + 1..page_size + 2]
+        );
+        assert!(
+            mapping
+                .dirty_pages(3 * page_size..4 * page_size)
+                .unwrap()
+                .is_empty()
+        );
+
+        mapping.clear_soft_dirty().unwrap();
+
+        mapping[3 * page_size] = 4;
+
+        assert_eq!(
+            mapping.dirty_pages(0..mapping.size()).unwrap(),
+            vec![3 * page_size..4 * page_size]
+        );
+        assert_eq!(mapping[page_size], 2);
+        assert_eq!(mapping[3 * page_size], 4);
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn userfaultfd() {
+        use crate::{MmapOptions, PageFaultFlags, UffdFlags, UffdHandler, UffdRegisterMode};
+
+        let page_size = MmapOptions::page_size();
+        let mut mapping = MmapOptions::new(2 * page_size).unwrap().map_mut().unwrap();
+
+        let handler = UffdHandler::new(UffdFlags::CLOEXEC | UffdFlags::USER_MODE_ONLY).unwrap();
+        unsafe {
+            handler.register(
+                mapping.start()..mapping.end(),
+                UffdRegisterMode::MISSING | UffdRegisterMode::WRITE_PROTECT,
+            )
+        }
+        .unwrap();
+
+        let start = mapping.start();
+
+        std::thread::scope(|scope| {
+            let reader = scope.spawn(|| (mapping[0], mapping[page_size]));
+
+            for _ in 0..2 {
+                let fault = handler.next_fault().unwrap();
+
+                if fault.address() == start {
+                    unsafe { handler.copy(start, &vec![0x42; page_size]) }.unwrap();
+                } else {
+                    assert_eq!(fault.address(), start + page_size);
+                    unsafe { handler.zeropage(start + page_size..start + 2 * page_size) }.unwrap();
+                }
+            }
+
+            assert_eq!(reader.join().unwrap(), (0x42, 0));
+        });
+
+        unsafe { handler.writeprotect(start..start + page_size, true) }.unwrap();
+
+        std::thread::scope(|scope| {
+            let writer = scope.spawn(|| mapping[0] = 0x43);
+
+            let fault = handler.next_fault().unwrap();
+            assert_eq!(fault.address(), start);
+            assert!(fault.flags().contains(PageFaultFlags::WRITE_PROTECT));
+
+            unsafe { handler.writeprotect(start..start + page_size, false) }.unwrap();
+            writer.join().unwrap();
+        });
+
+        assert_eq!(mapping[0], 0x43);
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn process_memory() {
+        use crate::{MemoryAreas, MmapOptions, ProcessMemory};
+
+        let page_size = MmapOptions::page_size();
+        let mut mapping = isolated(page_size).map_mut().unwrap();
+        mapping[..4].copy_from_slice(&[1, 2, 3, 4]);
+
+        let memory = ProcessMemory::open(None).unwrap();
+        assert_eq!(memory.pid(), std::process::id());
+
+        // Process IDs cannot exceed 2^22.
+        assert!(ProcessMemory::open(Some(i32::MAX as u32)).is_err());
+
+        let mut bytes = [0u8; 4];
+        memory.read(mapping.start(), &mut bytes).unwrap();
+        assert_eq!(bytes, [1, 2, 3, 4]);
+
+        // Writing to read-only pages works through /proc/<pid>/mem.
+        let mapping = mapping.make_read_only().unwrap();
+        unsafe { memory.write(mapping.start() + 1, &[5, 6]) }.unwrap();
+        assert_eq!(mapping[..4], [1, 5, 6, 4]);
+
+        let area = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+        let contents = memory.read_area(&area).unwrap();
+        let offset = mapping.start() - area.start();
+        assert_eq!(contents[offset..offset + 4], [1, 5, 6, 4]);
+
+        // The memory of a process that has exited can no longer be read, even if its process ID
+        // gets reused.
+        let mut child = std::process::Command::new("sleep")
+            .arg("60")
+            .spawn()
+            .unwrap();
+        let memory = ProcessMemory::open(Some(child.id())).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(memory.read(mapping.start(), &mut bytes).is_err());
+    }
+
+    #[test]
+    fn memory_map_diff() {
+        use crate::{MemoryMap, MmapOptions, Protection};
+
+        let page_size = MmapOptions::page_size();
+        let before = MemoryMap::snapshot(None).unwrap();
+
+        let mapping = isolated(4 * page_size).map().unwrap();
+
+        let after = MemoryMap::snapshot(None).unwrap();
+        let diff = before.diff(&after);
+
+        let added = diff
+            .added()
+            .iter()
+            .find(|area| area.start() == mapping.start())
+            .unwrap();
+        assert_eq!(added.end(), mapping.end());
+        assert!(after.size_by_protection()[&Protection::READ] >= mapping.size());
+        assert!(after.size_by_path()[&None] >= mapping.size());
+        assert!(after.total_size() >= mapping.size());
+        assert!(after.diff(&after).is_empty());
+
+        let diff = after.diff(&before);
+        assert!(
+            diff.removed()
+                .iter()
+                .any(|area| area.start() == mapping.start())
+        );
+    }
+
+    #[test]
+    fn alignment() {
+        use crate::{Error, MmapOptions};
+
+        let alignment = 2 << 20;
+
+        let mapping = MmapOptions::new(MmapOptions::page_size())
+            .unwrap()
+            .with_alignment(alignment)
+            .map_mut()
+            .unwrap();
+        assert_eq!(mapping.start() % alignment, 0);
+
+        let mapping = MmapOptions::new(MmapOptions::page_size())
+            .unwrap()
+            .with_guard_pages(1, 1)
+            .with_alignment(alignment)
+            .map_mut()
+            .unwrap();
+        assert_eq!(mapping.start() % alignment, 0);
+
+        assert!(matches!(
+            MmapOptions::new(MmapOptions::page_size())
+                .unwrap()
+                .with_alignment(3 * MmapOptions::page_size())
+                .map_mut(),
+            Err(Error::InvalidAlignment)
+        ));
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn find_free() {
+        use crate::{MemoryAreas, MmapOptions};
+
+        let alignment = 2 << 20;
+        let size = 4 * MmapOptions::page_size();
+
+        // Look for free memory between 4 GiB and 8 GiB, which is typically not used by the
+        // executable or other memory mappings.
+        let candidates = MemoryAreas::find_free(size, alignment, 1 << 32..2 << 32).unwrap();
+        let candidate = candidates.first().unwrap();
+        assert_eq!(candidate.start % alignment, 0);
+        assert!(candidate.len() >= size);
+
+        let mapping = MmapOptions::new(size)
+            .unwrap()
+            .with_address(candidate.start)
+            .map_mut()
+            .unwrap();
+        assert_eq!(mapping.start(), candidate.start);
+    }
+
+    #[test]
+    fn exact_address() {
+        use crate::{Error, MmapOptions};
+
+        let size = MmapOptions::allocation_granularity();
+        let mapping = MmapOptions::new(size).unwrap().map_mut().unwrap();
+
+        assert!(matches!(
+            MmapOptions::new(size)
+                .unwrap()
+                .with_exact_address(mapping.start())
+                .map_mut(),
+            Err(Error::AddressInUse)
+        ));
+
+        let address = mapping.start();
+        drop(mapping);
+
+        // Other threads may have claimed the address range in the meantime.
+        match MmapOptions::new(size)
+            .unwrap()
+            .with_exact_address(address)
+            .map_mut()
+        {
+            Ok(mapping) => assert_eq!(mapping.start(), address),
+            Err(e) => assert!(matches!(e, Error::AddressInUse)),
+        }
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn anon_name() {
+        use crate::{Error, MemoryAreaKind, MemoryAreas, MmapOptions};
+
+        let mapping = match isolated(MmapOptions::page_size())
+            .with_name("arena:decoder")
+            .map_mut()
+        {
+            Ok(mapping) => mapping,
+            // The kernel has not been built with CONFIG_ANON_VMA_NAME.
+            Err(Error::InvalidOperation) => return,
+            Err(e) => panic!("{e}"),
+        };
+
+        let area = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+        assert_eq!(
+            area.kind(),
+            &MemoryAreaKind::AnonNamed("arena:decoder".to_string())
+        );
+
+        mapping.set_name(Some("arena:encoder")).unwrap();
+        let area = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+        assert_eq!(
+            area.kind(),
+            &MemoryAreaKind::AnonNamed("arena:encoder".to_string())
+        );
+
+        mapping.set_name(None).unwrap();
+        let area = MemoryAreas::query(mapping.start()).unwrap().unwrap();
+        assert_eq!(area.kind(), &MemoryAreaKind::Anonymous);
+
+        assert!(mapping.set_name(Some("[invalid]")).is_err());
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn anon_name_parsing() {
+        use crate::MemoryAreaKind;
+        use crate::os_impl::linux::parse_memory_area;
+
+        let area = parse_memory_area(
+            "7f0000000000-7f0000001000 rw-p 00000000 00:00 0                          [anon:arena:decoder]",
+        )
+        .unwrap();
+        assert_eq!(
+            area.kind(),
+            &MemoryAreaKind::AnonNamed("arena:decoder".to_string())
+        );
+        assert!(area.path().is_none());
+
+        let area = parse_memory_area(
+            "7f0000001000-7f0000002000 rw-s 00000000 00:01 1024                       [anon_shmem:x]",
+        )
+        .unwrap();
+        assert_eq!(area.kind(), &MemoryAreaKind::AnonNamed("x".to_string()));
+        assert!(area.path().is_none());
+
+        let area = parse_memory_area(
+            "7f0000002000-7f0000003000 r--p 00000000 00:00 0                          [uprobes]",
+        )
+        .unwrap();
+        assert_eq!(area.kind(), &MemoryAreaKind::Other("uprobes".to_string()));
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn protection_key() {
+        use crate::{Error, MmapOptions, ProtectionKey, ProtectionKeyRights};
+        use nix::sys::signal::Signal;
+        use nix::sys::wait::{WaitStatus, waitpid};
+        use nix::unistd::{ForkResult, fork};
+
+        let key = match ProtectionKey::new(ProtectionKeyRights::empty()) {
+            Ok(key) => key,
+            // The CPU or the kernel does not support memory protection keys.
+            Err(Error::InvalidOperation) => return,
+            Err(e) => panic!("{e}"),
+        };
+
+        let page_size = MmapOptions::page_size();
+        let mut mapping = MmapOptions::new(2 * page_size)
+            .unwrap()
+            .with_protection_key(&key)
+            .map_mut()
+            .unwrap();
+        mapping[0] = 1;
+
+        let mut other = MmapOptions::new(page_size).unwrap().map_mut().unwrap();
+        other.assign_key(0..page_size, &key).unwrap();
+        other[0] = 2;
+
+        match key.rights() {
+            Ok(rights) => assert_eq!(rights, ProtectionKeyRights::empty()),
+            // Only supported on x86-64.
+            Err(Error::InvalidOperation) => return,
+            Err(e) => panic!("{e}"),
+        }
+
+        // The child process disables writes and then writes to the memory mapping, which should
+        // fault. Disable core dumps, as the child process is expected to crash.
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => unsafe {
+                libc::prctl(libc::PR_SET_DUMPABLE, 0);
+                let _ = key.set_rights(ProtectionKeyRights::DISABLE_WRITE);
+                std::ptr::write_volatile(mapping.as_mut_ptr(), 4);
+                libc::_exit(0);
+            },
+            ForkResult::Parent { child } => {
+                assert!(matches!(
+                    waitpid(child, None).unwrap(),
+                    WaitStatus::Signaled(_, Signal::SIGSEGV, _)
+                ));
+            }
+        }
+
+        // Writes would now fault, but reads are still allowed.
+        unsafe { key.set_rights(ProtectionKeyRights::DISABLE_WRITE) }.unwrap();
+        assert_eq!(key.rights().unwrap(), ProtectionKeyRights::DISABLE_WRITE);
+        assert_eq!(mapping[0], 1);
+        assert_eq!(other[0], 2);
+
+        unsafe { key.set_rights(ProtectionKeyRights::empty()) }.unwrap();
+        mapping[page_size] = 3;
+        assert_eq!(mapping[page_size], 3);
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn protection_key_exhausted() {
+        use crate::{Error, ProtectionKey, ProtectionKeyRights};
+
+        // Allocating more protection keys than the hardware provides, or any at all without
+        // support for them, fails in the same way.
+        let mut keys = vec![];
+
+        let error = loop {
+            match ProtectionKey::new(ProtectionKeyRights::empty()) {
+                Ok(key) if keys.len() < 64 => keys.push(key),
+                Ok(_) => panic!("more protection keys have been allocated than available"),
+                Err(e) => break e,
+            }
+        };
+
+        assert!(matches!(error, Error::InvalidOperation));
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn protection_key_unaligned_file_offset() {
+        use crate::{Error, MmapOptions, ProtectionKey, ProtectionKeyRights};
+
+        let key = match ProtectionKey::new(ProtectionKeyRights::empty()) {
+            Ok(key) => key,
+            // The CPU or the kernel does not support memory protection keys.
+            Err(Error::InvalidOperation) => return,
+            Err(e) => panic!("{e}"),
+        };
+
+        let page_size = MmapOptions::page_size();
+        let file = tempfile::tempfile().unwrap();
+        file.set_len(3 * page_size as u64).unwrap();
+
+        let mut mapping = unsafe {
+            MmapOptions::new(2 * page_size)
+                .unwrap()
+                .with_file(&file, 100)
+                .map_mut()
+                .unwrap()
+        };
+
+        // The range has to end at a page boundary of the underlying mapping.
+        assert!(matches!(
+            mapping.assign_key(0..page_size, &key),
+            Err(Error::InvalidOffset)
+        ));
+
+        mapping.assign_key(0..page_size - 100, &key).unwrap();
+        mapping[0] = 1;
+        assert_eq!(mapping[0], 1);
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[test]
+    fn seal() {
+        use crate::{Error, MmapOptions, Protection};
+
+        let page_size = MmapOptions::page_size();
+        let mut mapping = isolated(2 * page_size).map().unwrap();
+
+        match mapping.seal() {
+            Ok(()) => (),
+            // The kernel does not support mseal().
+            Err(Error::InvalidOperation) => return,
+            Err(e) => panic!("{e}"),
+        }
+
+        assert!(mapping.is_sealed());
+        assert!(matches!(
+            mapping.protect_range(0..page_size, Protection::READ | Protection::EXECUTE),
+            Err(Error::Sealed)
+        ));
+        assert!(matches!(mapping.split_off(page_size), Err(Error::Sealed)));
+
+        let mapping = match mapping.make_mut() {
+            Err((mapping, Error::Sealed)) => mapping,
+            _ => panic!("sealed memory mapping has been made mutable"),
+        };
+
+        assert_eq!(mapping[0], 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn jit_memory() {
+        use crate::{JitMemory, MemoryAreas, MmapOptions, Protection};
+
+        let mut jit = JitMemory::new(MmapOptions::page_size()).unwrap();
+
+        assert_ne!(jit.writable().start(), jit.executable().start());
+
+        // mov eax, 42; ret
+        #[cfg(target_arch = "x86_64")]
+        let code: &[u8] = &[0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3];
+        // mov w0, #42; ret
+        #[cfg(target_arch = "aarch64")]
+        let code: &[u8] = &[0x40, 0x05, 0x80, 0x52, 0xc0, 0x03, 0x5f, 0xd6];
+        #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+        let code: &[u8] = &[0x2a];
+
+        jit.as_mut_slice()[..code.len()].copy_from_slice(code);
+        jit.commit().unwrap();
+
+        assert_eq!(&jit.executable()[..code.len()], code);
+
+        let area = MemoryAreas::query(jit.as_exec_ptr() as usize)
+            .unwrap()
+            .unwrap();
+
+        assert!(area.protection.contains(Protection::EXECUTE));
+        assert!(!area.protection.contains(Protection::WRITE));
+
+        #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+        {
+            let f: extern "C" fn() -> u32 = unsafe { std::mem::transmute(jit.as_exec_ptr()) };
+
+            assert_eq!(f(), 42);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ring_mapping() {
+        use crate::{Error, MmapOptions, RingMapping};
+
+        let size = MmapOptions::page_size();
+
+        assert!(matches!(
+            RingMapping::new(size + 1),
+            Err(Error::InvalidSize)
+        ));
+
+        let mut ring = RingMapping::new(size).unwrap();
+
+        assert_eq!(ring.size(), size);
+
+        ring.as_mut_slice(0, 4).unwrap().copy_from_slice(b"ring");
+        assert_eq!(ring.as_slice(size - 1, 5).unwrap()[1..], *b"ring");
+
+        // Writes that wrap around the end appear at the start.
+        ring.as_mut_slice(size - 2, 4)
+            .unwrap()
+            .copy_from_slice(b"wrap");
+        assert_eq!(ring.as_slice(0, 2).unwrap(), b"ap");
+        assert_eq!(ring.as_slice(size - 2, 2).unwrap(), b"wr");
+
+        assert!(matches!(ring.as_slice(size, 1), Err(Error::InvalidOffset)));
+        assert!(matches!(
+            ring.as_slice(0, size + 1),
+            Err(Error::InvalidSize)
+        ));
+    }
 }
@@ -1,8 +1,12 @@
-use crate::areas::MemoryAreas;
+use crate::areas::Protection;
 use crate::error::Error;
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use crate::page_map::PageMap;
 use bitflags::bitflags;
 use std::fs::File;
-use std::ops::{Deref, DerefMut, Range};
+use std::ops::{Bound, Deref, DerefMut, Range, RangeBounds};
+use std::path::Path;
+use std::sync::Arc;
 
 #[cfg(unix)]
 use crate::os_impl::unix as platform;
@@ -197,6 +201,115 @@ impl PageSize {
     pub const _16G: Self = Self(34);
 }
 
+/// The policy to apply when resizing a memory mapping.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RemapPolicy {
+    /// The memory mapping may be moved to a different virtual address if it cannot be resized in
+    /// place.
+    MayMove,
+    /// The memory mapping must be resized in place, i.e. the start address must remain the same.
+    InPlaceOnly,
+}
+
+/// The advice to give to the operating system about the expected use of a memory mapping.
+///
+/// Not all advice is available on every platform. See [`Mmap::advise()`] for more information.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Advice {
+    /// No special treatment, which is the default.
+    Normal,
+    /// Expect page references in random order.
+    Random,
+    /// Expect page references in sequential order.
+    Sequential,
+    /// Expect the pages to be accessed in the near future.
+    WillNeed,
+    /// Do not expect the pages to be accessed in the near future. On Linux, subsequent accesses to
+    /// private anonymous pages will yield zero-filled pages.
+    DontNeed,
+    /// The pages are no longer needed and may be freed lazily. Until the pages are freed, they
+    /// keep their contents.
+    Free,
+    /// Deactivate the pages, such that they are reclaimed first under memory pressure.
+    Cold,
+    /// Reclaim the pages immediately.
+    PageOut,
+    /// Free the pages and their backing store.
+    Remove,
+    /// Enable transparent huge pages for the pages.
+    HugePage,
+    /// Disable transparent huge pages for the pages.
+    NoHugePage,
+    /// Do not make the pages available to a child process after a `fork()`.
+    DontFork,
+    /// Undo the effect of [`Advice::DontFork`].
+    DoFork,
+    /// Provide zero-filled pages to a child process after a `fork()`.
+    WipeOnFork,
+    /// Undo the effect of [`Advice::WipeOnFork`].
+    KeepOnFork,
+    /// Exclude the pages from a core dump.
+    DontDump,
+    /// Undo the effect of [`Advice::DontDump`].
+    DoDump,
+    /// Enable kernel samepage merging (KSM) for the pages.
+    Mergeable,
+    /// Undo the effect of [`Advice::Mergeable`].
+    Unmergeable,
+    /// Populate the pages for reading, such that subsequent reads do not cause page faults.
+    PopulateRead,
+    /// Populate the pages for writing, such that subsequent writes do not cause page faults.
+    PopulateWrite,
+}
+
+/// Describes which pages of a memory mapping are resident in RAM, i.e. which pages can be
+/// accessed without causing a major page fault. See [`Mmap::residency()`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ResidencyBitmap {
+    pages: Vec<bool>,
+}
+
+impl ResidencyBitmap {
+    /// Returns the number of pages covered.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns `true` if no pages are covered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// Returns `true` if the page at the given index is resident. Returns `false` if the index is
+    /// out of bounds.
+    #[inline]
+    pub fn is_resident(&self, index: usize) -> bool {
+        self.pages.get(index).copied().unwrap_or(false)
+    }
+
+    /// Returns an iterator over the residency of the pages.
+    pub fn iter(&self) -> impl Iterator<Item = bool> + '_ {
+        self.pages.iter().copied()
+    }
+
+    /// Returns the number of resident pages.
+    pub fn resident_pages(&self) -> usize {
+        self.pages.iter().filter(|resident| **resident).count()
+    }
+
+    /// Returns the fraction of the pages that is resident, ranging from `0.0` to `1.0`. This
+    /// returns `0.0` if no pages are covered.
+    pub fn resident_fraction(&self) -> f64 {
+        if self.pages.is_empty() {
+            return 0.0;
+        }
+
+        self.resident_pages() as f64 / self.pages.len() as f64
+    }
+}
+
 impl TryFrom<PageSizes> for PageSize {
     type Error = Error;
 
@@ -210,7 +323,7 @@ impl TryFrom<PageSizes> for PageSize {
 }
 
 macro_rules! reserved_mmap_impl {
-    ($t:ident) => {
+    ($t:ident, $required:expr) => {
         impl $t {
             /// Returns the start address of this mapping.
             #[inline]
@@ -242,34 +355,27 @@ macro_rules! reserved_mmap_impl {
                 self.inner.size()
             }
 
+            /// Returns the file backing this mapping, if it has been mapped using
+            /// [`MmapOptions::with_owned_file()`].
+            #[inline]
+            pub fn file(&self) -> Option<&File> {
+                self.inner.file()
+            }
+
             /// Merges the memory maps into one. The memory maps must be adjacent to each other and
             /// share the same attributes and backing. On success, this consumes the other memory map
             /// object. Otherwise, this returns an error together with the original memory map that
             /// failed to be merged.
-            pub fn merge(&mut self, other: Self) -> Result<(), (Error, Self)> {
+            pub fn merge(&mut self, mut other: Self) -> Result<(), (Error, Self)> {
                 // Ensure the memory maps are adjacent.
                 if self.end() != other.start() {
                     return Err((Error::MustBeAdjacent, other));
                 }
 
-                // Ensure the protection attributes match.
-                let region = match MemoryAreas::query(self.start()) {
-                    Ok(Some(region)) => region,
-                    Ok(None) => return Err((Error::AttributeMismatch, other)),
-                    Err(e) => return Err((e, other)),
-                };
-
-                let other_region = match MemoryAreas::query(other.start()) {
-                    Ok(Some(region)) => region,
-                    Ok(None) => return Err((Error::AttributeMismatch, other)),
-                    Err(e) => return Err((e, other)),
-                };
-
-                if region.protection != other_region.protection {
-                    return Err((Error::AttributeMismatch, other));
-                }
-
-                if let Err(e) = self.inner.merge(&other.inner) {
+                // Ensure the attributes match, which includes the protection both memory maps
+                // have been mapped with. Any protection set through `protect_range()` is carried
+                // over into the merged memory map.
+                if let Err(e) = self.inner.merge(&mut other.inner) {
                     return Err((e, other));
                 }
 
@@ -278,6 +384,77 @@ macro_rules! reserved_mmap_impl {
                 Ok(())
             }
 
+            /// Changes the protection of the given byte range of the memory map without splitting
+            /// it. The range must be page size aligned, but may end at the end of the memory map.
+            ///
+            /// The protection of each page is tracked, such that [`Self::merge()`] and
+            /// [`Self::split_off()`] carry it over, while remapping the memory map as a whole,
+            /// e.g. using `make_mut()`, resets the protection of all pages.
+            ///
+            /// The protection cannot be weaker than what the type of the memory map hands out
+            /// access to, i.e. it must include [`Protection::READ`] for immutable memory maps and
+            /// [`Protection::READ`] and [`Protection::WRITE`] for mutable memory maps. Otherwise,
+            /// [`Error::InvalidOperation`] is returned. Use `make_none()` or `make_read_only()` to
+            /// remove access from the memory map as a whole instead.
+            ///
+            /// Making pages both writable and executable requires the [`UnsafeMmapFlags::JIT`]
+            /// flag to be set. If the pages are made executable, then the instruction cache is
+            /// flushed.
+            pub fn protect_range(
+                &mut self,
+                range: Range<usize>,
+                protection: Protection,
+            ) -> Result<(), Error> {
+                if !protection.contains($required) {
+                    return Err(Error::InvalidOperation);
+                }
+
+                self.inner.protect_range(range, protection)?;
+
+                if protection.contains(Protection::EXECUTE) {
+                    self.inner.flush_icache()?;
+                }
+
+                Ok(())
+            }
+
+            /// Names the anonymous memory map, such that it shows up as `[anon:<name>]` in
+            /// `/proc/<pid>/maps` and as [`MemoryAreaKind::AnonNamed`] in [`MemoryAreas`].
+            /// Passing `None` removes the name. The name can be at most 79 bytes long and may only
+            /// contain printable ASCII characters other than `\`, `` ` ``, `$`, `[` and `]`.
+            ///
+            /// This uses `prctl(PR_SET_VMA_ANON_NAME)`, which requires Linux 5.17 built with
+            /// `CONFIG_ANON_VMA_NAME`, otherwise this returns [`Error::InvalidOperation`].
+            ///
+            /// [`MemoryAreaKind::AnonNamed`]: crate::MemoryAreaKind::AnonNamed
+            /// [`MemoryAreas`]: crate::MemoryAreas
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            pub fn set_name(&self, name: Option<&str>) -> Result<(), Error> {
+                self.inner.set_name(name)
+            }
+
+            /// Returns the protection of the page at the given byte offset as tracked by this
+            /// memory map.
+            pub fn protection_at(&self, offset: usize) -> Result<Protection, Error> {
+                self.inner.protection_at(offset)
+            }
+
+            /// Resizes the memory map to the given size in bytes. With [`RemapPolicy::MayMove`] the
+            /// memory map may be moved to a different address if it cannot be grown in place, in
+            /// which case any pointers into the memory map are invalidated.
+            ///
+            /// On Linux, this uses `mremap()`. On other Unix platforms, the memory map can only be
+            /// shrunk or grown in place by mapping the pages that directly follow it, such that
+            /// [`RemapPolicy::MayMove`] behaves like [`RemapPolicy::InPlaceOnly`]. On Microsoft
+            /// Windows, this is not supported. [`Error::InvalidOperation`] is returned when the
+            /// memory map cannot be resized.
+            ///
+            /// Memory maps backed by a file can only be shrunk, as the pages beyond the end of the
+            /// file would raise `SIGBUS` when accessed.
+            pub fn resize(&mut self, new_len: usize, policy: RemapPolicy) -> Result<(), Error> {
+                self.inner.resize(new_len, policy)
+            }
+
             /// Splits the memory map into two at the given byte offset. The byte offset must be
             /// page size aligned.
             ///
@@ -327,6 +504,9 @@ macro_rules! mmap_impl {
             ///
             /// On other platforms, the flush operation is synchronous, i.e. this waits until the
             /// flush operation completes.
+            ///
+            /// If the memory mapping owns its file, see [`MmapOptions::with_owned_file()`], then
+            /// this is followed by a call to [`File::sync_data`].
             pub fn flush(&self, range: Range<usize>) -> Result<(), Error> {
                 self.inner.flush(range)
             }
@@ -351,6 +531,46 @@ macro_rules! mmap_impl {
                 self.inner.flush_icache()
             }
 
+            /// Advises the operating system about the expected use of the given byte range of the
+            /// memory mapping. The start of the range must be page size aligned.
+            ///
+            /// On Unix platforms, this uses `madvise()`. On Microsoft Windows, only
+            /// [`Advice::WillNeed`] and [`Advice::Free`] are supported. If the advice is not
+            /// available on the current platform, this returns [`Error::UnsupportedAdvice`].
+            pub fn advise(&mut self, range: Range<usize>, advice: Advice) -> Result<(), Error> {
+                self.inner.advise(range, advice)
+            }
+
+            /// Returns which pages covering the given byte range of the memory mapping are
+            /// resident in RAM, where the first page is the page containing the start of the
+            /// range. This can be used to decide whether to prefetch the pages using
+            /// [`Advice::WillNeed`].
+            ///
+            /// On Unix platforms, this uses `mincore()`, which for file mappings reports whether
+            /// the pages are in the page cache. On Microsoft Windows, this uses
+            /// `QueryWorkingSetEx()`, which reports whether the pages are in the working set of
+            /// the current process.
+            pub fn residency(&self, range: Range<usize>) -> Result<ResidencyBitmap, Error> {
+                Ok(ResidencyBitmap {
+                    pages: self.inner.residency(range)?,
+                })
+            }
+
+            /// Assigns the protection key to the pages covering the given byte range of the memory
+            /// mapping, such that the access rights of the current thread to these pages can be
+            /// changed using [`ProtectionKey::set_rights()`]. The start of the range must be page
+            /// size aligned.
+            ///
+            /// [`ProtectionKey::set_rights()`]: crate::ProtectionKey::set_rights
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            pub fn assign_key(
+                &mut self,
+                range: Range<usize>,
+                key: &crate::ProtectionKey,
+            ) -> Result<(), Error> {
+                self.inner.assign_key(range, key)
+            }
+
             /// Remaps this memory mapping as inaccessible.
             ///
             /// In case of failure, this returns the ownership of `self`.
@@ -458,6 +678,66 @@ macro_rules! mmap_impl {
     };
 }
 
+#[cfg(any(target_os = "android", target_os = "linux"))]
+macro_rules! seal_impl {
+    ($t:ident) => {
+        impl $t {
+            /// Seals the memory mapping using `mseal()`, such that its protection and size can no
+            /// longer be changed, e.g. to harden JIT output or security-sensitive tables once they
+            /// have been set up. Any guard pages are sealed as well. Afterwards, changing the
+            /// protection, resizing, splitting and merging the memory mapping fail with
+            /// [`Error::Sealed`].
+            ///
+            /// Sealed memory cannot be unmapped, such that the memory remains mapped until the
+            /// process exits, even after this object has been dropped. This requires Linux 6.10
+            /// on a 64-bit platform and returns [`Error::InvalidOperation`] otherwise.
+            pub fn seal(&mut self) -> Result<(), Error> {
+                self.inner.seal()
+            }
+
+            /// Returns `true` if the memory mapping has been sealed using [`Self::seal()`].
+            #[inline]
+            pub fn is_sealed(&self) -> bool {
+                self.inner.is_sealed()
+            }
+        }
+    };
+}
+
+#[cfg(feature = "bytemuck")]
+macro_rules! typed_impl {
+    ($t:ident) => {
+        impl $t {
+            /// Interprets the start of the mapping as a reference to `T`.
+            ///
+            /// Returns [`Error::InvalidSize`] if the mapping is too small to hold `T` and
+            /// [`Error::InvalidAlignment`] if the mapping is not suitably aligned for `T`.
+            pub fn as_typed<T: bytemuck::AnyBitPattern>(&self) -> Result<&T, Error> {
+                let bytes = self
+                    .get(..std::mem::size_of::<T>())
+                    .ok_or(Error::InvalidSize)?;
+
+                Ok(bytemuck::try_from_bytes(bytes)?)
+            }
+
+            /// Interprets the given byte range of the mapping as a slice of `T`.
+            ///
+            /// Returns [`Error::InvalidOffset`] if the range is out of bounds,
+            /// [`Error::InvalidSize`] if the length of the range is not a multiple of the size of
+            /// `T` and [`Error::InvalidAlignment`] if the start of the range is not suitably
+            /// aligned for `T`.
+            pub fn as_typed_slice<T: bytemuck::AnyBitPattern>(
+                &self,
+                range: Range<usize>,
+            ) -> Result<&[T], Error> {
+                let bytes = self.get(range).ok_or(Error::InvalidOffset)?;
+
+                Ok(bytemuck::try_cast_slice(bytes)?)
+            }
+        }
+    };
+}
+
 /// Represents an inaccessible memory mapping.
 #[derive(Debug)]
 pub struct MmapNone {
@@ -465,7 +745,9 @@ pub struct MmapNone {
 }
 
 mmap_impl!(MmapNone);
-reserved_mmap_impl!(MmapNone);
+reserved_mmap_impl!(MmapNone, Protection::empty());
+#[cfg(any(target_os = "android", target_os = "linux"))]
+seal_impl!(MmapNone);
 
 /// Represents an immutable memory mapping.
 #[derive(Debug)]
@@ -474,9 +756,50 @@ pub struct Mmap {
 }
 
 mmap_impl!(Mmap);
-reserved_mmap_impl!(Mmap);
+reserved_mmap_impl!(Mmap, Protection::READ);
+#[cfg(any(target_os = "android", target_os = "linux"))]
+seal_impl!(Mmap);
+#[cfg(feature = "bytemuck")]
+typed_impl!(Mmap);
 
 impl Mmap {
+    /// Maps the given byte range of the [`File`] as immutable, where the range may extend up to the
+    /// end of the file as determined by its metadata. The range does not have to be aligned, see
+    /// [`MmapOptions::with_file()`].
+    ///
+    /// Returns [`Error::InvalidSize`] if the file or the range is empty, or if the range extends
+    /// beyond the end of the file.
+    ///
+    /// # Safety
+    ///
+    /// This remains `unsafe`, even though the size is derived from the file, as this cannot
+    /// prevent the file from being changed while it is mapped. Any other process may truncate the
+    /// file, after which accessing the pages beyond the new end of the file raises `SIGBUS` on
+    /// Unix platforms, or modify the file, which changes the contents of the immutable slice. The
+    /// caller must ensure that neither happens, see [`MmapOptions::with_file()`].
+    pub unsafe fn open_file(file: &File, range: impl RangeBounds<u64>) -> Result<Self, Error> {
+        let (offset, size) = file_window(file.metadata()?.len(), range)?;
+
+        unsafe { MmapOptions::new(size)?.with_file(file, offset) }.map()
+    }
+
+    /// Opens the file at the given path for reading and maps the given byte range of it as
+    /// immutable. The memory mapping owns the file, see [`MmapOptions::with_owned_file()`]. See
+    /// [`Mmap::open_file()`] for more information.
+    ///
+    /// # Safety
+    ///
+    /// See [`Mmap::open_file()`].
+    pub unsafe fn open_path(
+        path: impl AsRef<Path>,
+        range: impl RangeBounds<u64>,
+    ) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let (offset, size) = file_window(file.metadata()?.len(), range)?;
+
+        unsafe { MmapOptions::new(size)?.with_owned_file(file, offset) }.map()
+    }
+
     /// Extracts a slice containing the entire mapping.
     ///
     /// This is equivalent to `&mapping[..]`.
@@ -486,6 +809,54 @@ impl Mmap {
     }
 }
 
+/// Determines the file offset and the size of the memory mapping for the given byte range of a
+/// file of the given length.
+fn file_window(len: u64, range: impl RangeBounds<u64>) -> Result<(u64, usize), Error> {
+    if len == 0 {
+        return Err(Error::InvalidSize);
+    }
+
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.checked_add(1).ok_or(Error::InvalidOffset)?,
+        Bound::Unbounded => 0,
+    };
+
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.checked_add(1).ok_or(Error::InvalidSize)?,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+
+    if start > end {
+        return Err(Error::InvalidOffset);
+    }
+
+    if start == end || end > len {
+        return Err(Error::InvalidSize);
+    }
+
+    Ok((start, (end - start).try_into()?))
+}
+
+/// Creates anonymous shared memory of the given size in bytes that can be mapped multiple times,
+/// e.g. to map the same pages at different addresses. This uses [`SharedMemory`] on Linux and
+/// Android, and an unlinked [`NamedSharedMemory`] on other Unix platforms. The name is only used
+/// for debugging purposes.
+///
+/// [`SharedMemory`]: crate::SharedMemory
+/// [`NamedSharedMemory`]: crate::NamedSharedMemory
+#[cfg(unix)]
+pub(crate) fn anonymous_shared_memory(name: &str, size: usize) -> Result<File, Error> {
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    let file = crate::SharedMemory::new(name, size, crate::SharedMemoryFlags::CLOEXEC)?.into_file();
+
+    #[cfg(not(any(target_os = "android", target_os = "linux")))]
+    let file = crate::NamedSharedMemory::create_unlinked(name, size)?;
+
+    Ok(file)
+}
+
 impl Deref for Mmap {
     type Target = [u8];
 
@@ -527,7 +898,9 @@ pub struct MmapMut {
 }
 
 mmap_impl!(MmapMut);
-reserved_mmap_impl!(MmapMut);
+reserved_mmap_impl!(MmapMut, Protection::READ | Protection::WRITE);
+#[cfg(feature = "bytemuck")]
+typed_impl!(MmapMut);
 
 impl MmapMut {
     /// Extracts a slice containing the entire mapping.
@@ -545,6 +918,108 @@ impl MmapMut {
     pub fn as_mut_slice(&mut self) -> &mut [u8] {
         &mut self[..]
     }
+
+    /// Interprets the start of the mapping as a mutable reference to `T`.
+    ///
+    /// See [`MmapMut::as_typed()`] for the conditions under which this fails.
+    #[cfg(feature = "bytemuck")]
+    pub fn as_typed_mut<T: bytemuck::AnyBitPattern + bytemuck::NoUninit>(
+        &mut self,
+    ) -> Result<&mut T, Error> {
+        let bytes = self
+            .get_mut(..std::mem::size_of::<T>())
+            .ok_or(Error::InvalidSize)?;
+
+        Ok(bytemuck::try_from_bytes_mut(bytes)?)
+    }
+
+    /// Interprets the given byte range of the mapping as a mutable slice of `T`.
+    ///
+    /// See [`MmapMut::as_typed_slice()`] for the conditions under which this fails.
+    #[cfg(feature = "bytemuck")]
+    pub fn as_typed_slice_mut<T: bytemuck::AnyBitPattern + bytemuck::NoUninit>(
+        &mut self,
+        range: Range<usize>,
+    ) -> Result<&mut [T], Error> {
+        let bytes = self.get_mut(range).ok_or(Error::InvalidOffset)?;
+
+        Ok(bytemuck::try_cast_slice_mut(bytes)?)
+    }
+
+    /// Clears the soft-dirty bits of the pages, such that [`MmapMut::dirty_pages()`] only reports
+    /// the pages that have been written to since. This can be used to take incremental snapshots
+    /// of the memory mapping, by only copying the dirty pages at every checkpoint.
+    ///
+    /// Note that the kernel only allows clearing the soft-dirty bits for the entire process, such
+    /// that this affects any other memory mapping that is being tracked as well.
+    ///
+    /// If the kernel has not been built with `CONFIG_MEM_SOFT_DIRTY`, this falls back to
+    /// write-protecting the pages of this memory mapping and recording the first write to every
+    /// page in a `SIGSEGV` handler, which is installed once for the process and forwards any other
+    /// faults to the handler that was installed before. In that case:
+    ///
+    /// - System calls writing to the pages, e.g. `read()`, fail with `EFAULT` rather than being
+    ///   recorded, until the pages have been written to by the process itself.
+    /// - The tracking stops when the protection of the memory mapping changes, or when it is
+    ///   resized, split, merged or sealed, after which all pages are reported as dirty again.
+    /// - At most 64 memory mappings can be tracked at the same time, otherwise this returns
+    ///   [`Error::InvalidOperation`].
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn clear_soft_dirty(&mut self) -> Result<(), Error> {
+        if PageMap::soft_dirty_supported()? {
+            PageMap::clear_soft_dirty(None)
+        } else {
+            self.inner.track_writes()
+        }
+    }
+
+    /// Returns the byte ranges of the pages covering the given byte range of the memory mapping
+    /// that have been written to since the soft-dirty bits were last cleared using
+    /// [`MmapMut::clear_soft_dirty()`]. Adjacent dirty pages are coalesced into a single range
+    /// and the ranges are clamped to the given byte range.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn dirty_pages(&self, range: Range<usize>) -> Result<Vec<Range<usize>>, Error> {
+        if range.start > range.end || range.end > self.size() {
+            return Err(Error::InvalidOffset);
+        }
+
+        if range.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let page_size = MmapOptions::page_size();
+        let start = self.start();
+
+        let pages: Vec<usize> = if PageMap::soft_dirty_supported()? {
+            PageMap::open(None)?
+                .query_range(start + range.start..start + range.end)?
+                .into_iter()
+                .filter(|page| page.soft_dirty())
+                .map(|page| page.address())
+                .collect()
+        } else {
+            let first = (start + range.start) / page_size * page_size;
+
+            (first..start + range.end)
+                .step_by(page_size)
+                .filter(|&address| self.inner.is_written(address))
+                .collect()
+        };
+
+        let mut dirty: Vec<Range<usize>> = vec![];
+
+        for address in pages {
+            let page_start = (address.max(start) - start).max(range.start);
+            let page_end = (address + page_size - start).min(range.end);
+
+            match dirty.last_mut() {
+                Some(last) if last.end == page_start => last.end = page_end,
+                _ => dirty.push(page_start..page_end),
+            }
+        }
+
+        Ok(dirty)
+    }
 }
 
 impl TryFrom<Mmap> for MmapMut {
@@ -626,17 +1101,37 @@ impl<'a> MmapOptions<'a> {
         platform::MmapOptions::allocation_granularity()
     }
 
-    /// The desired address at which the memory should be mapped.
+    /// The desired address at which the memory should be mapped. This is only a hint, such that
+    /// the memory may be mapped elsewhere if the address range is already in use. See
+    /// [`MmapOptions::with_exact_address()`] to require the address.
     pub fn with_address(self, address: usize) -> Self {
         Self {
             inner: self.inner.with_address(address),
         }
     }
 
+    /// The address at which the memory must be mapped. Unlike [`UnsafeMmapFlags::MAP_FIXED`],
+    /// this never replaces existing memory mappings, but fails with [`Error::AddressInUse`] if
+    /// the address range is already in use.
+    ///
+    /// On Linux, this uses `MAP_FIXED_NOREPLACE`. On other Unix platforms, the memory mapping is
+    /// unmapped again if the operating system placed it elsewhere. On Microsoft Windows, the
+    /// address must be aligned to [`MmapOptions::allocation_granularity()`].
+    pub fn with_exact_address(self, address: usize) -> Self {
+        Self {
+            inner: self.inner.with_exact_address(address),
+        }
+    }
+
     /// Whether the memory mapping should be backed by a [`File`] or not. If the memory mapping
     /// should be mapped by a [`File`], then the user can also specify the offset within the file
     /// at which the mapping should start.
     ///
+    /// The offset does not have to be aligned. The memory mapping then starts at the preceding
+    /// multiple of [`MmapOptions::allocation_granularity()`], while [`Mmap::as_ptr()`] and
+    /// [`Mmap::size()`] only cover the bytes starting at the offset. The memory mapping can only
+    /// be split at page boundaries within the underlying memory mapping.
+    ///
     /// On Microsoft Windows, it may not be possible to extend the protection beyond the access
     /// mask that has been used to open the file. For instance, if a file has been opened with read
     /// access, then [`Mmap::make_mut()`] will not work. Furthermore, [`std::fs::OpenOptions`] does
@@ -671,6 +1166,44 @@ impl<'a> MmapOptions<'a> {
         }
     }
 
+    /// Maps the given shared memory, such as `SharedMemory` or `NamedSharedMemory`, at the
+    /// specified offset. This is equivalent to calling [`MmapOptions::with_file()`] with the
+    /// underlying [`File`] and [`MmapFlags::SHARED`], such that any modifications are visible to
+    /// the other processes mapping the shared memory.
+    ///
+    /// # Safety
+    ///
+    /// See [`MmapOptions::with_file()`]. Unless the shared memory has been sealed against writes,
+    /// other processes may modify its contents at any time.
+    #[cfg(unix)]
+    pub unsafe fn with_shared_memory<S: AsRef<File>>(
+        self,
+        shared_memory: &'a S,
+        offset: u64,
+    ) -> Self {
+        Self {
+            inner: self
+                .inner
+                .with_file(shared_memory.as_ref(), offset)
+                .with_flags(MmapFlags::SHARED),
+        }
+    }
+
+    /// Whether the memory mapping should be backed by a [`File`] that is owned by the memory
+    /// mapping. This is similar to [`MmapOptions::with_file()`], except that the file is kept open
+    /// for as long as the memory mapping, or any memory mapping split off from it, exists. The
+    /// file can then be retrieved using [`Mmap::file()`], and [`Mmap::flush()`] also flushes the
+    /// file data to the disk.
+    ///
+    /// # Safety
+    ///
+    /// See [`MmapOptions::with_file()`].
+    pub unsafe fn with_owned_file(self, file: impl Into<Arc<File>>, offset: u64) -> Self {
+        Self {
+            inner: self.inner.with_owned_file(file.into(), offset),
+        }
+    }
+
     /// The desired configuration of the mapping. See [`MmapFlags`] for available options.
     pub fn with_flags(self, flags: MmapFlags) -> Self {
         Self {
@@ -696,6 +1229,55 @@ impl<'a> MmapOptions<'a> {
         }
     }
 
+    /// Surrounds the memory mapping with the given number of inaccessible guard pages before and
+    /// after it, e.g. to catch stack overflows. The guard pages are owned by the memory mapping
+    /// and unmapped together with it, while [`Mmap::as_ptr()`] and [`Mmap::size()`] only cover
+    /// the usable memory in between.
+    ///
+    /// Memory mappings with guard pages cannot be resized. On Microsoft Windows, guard pages are
+    /// not supported for file mappings or when using huge pages.
+    pub fn with_guard_pages(self, before: usize, after: usize) -> Self {
+        Self {
+            inner: self.inner.with_guard_pages(before, after),
+        }
+    }
+
+    /// Aligns the start of the memory mapping to the given alignment, which must be a power of
+    /// two, e.g. 2 MiB to allow transparent huge pages to back the memory mapping entirely. If an
+    /// address has been specified using [`MmapOptions::with_address()`], then it must be aligned
+    /// as well. For file mappings with an unaligned offset, the alignment applies to the start of
+    /// the underlying memory mapping, see [`MmapOptions::with_file()`].
+    ///
+    /// On Unix platforms, this reserves a larger address range and trims the excess. On Microsoft
+    /// Windows, this searches for a suitable address range by reserving and releasing it first,
+    /// such that the memory mapping may fail if another thread maps memory at that address in the
+    /// meantime.
+    pub fn with_alignment(self, alignment: usize) -> Self {
+        Self {
+            inner: self.inner.with_alignment(alignment),
+        }
+    }
+
+    /// Names the anonymous memory mapping, such that it shows up as `[anon:<name>]` in
+    /// `/proc/<pid>/maps`. See [`Mmap::set_name()`] for the requirements on the name.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn with_name(self, name: &str) -> Self {
+        Self {
+            inner: self.inner.with_name(name),
+        }
+    }
+
+    /// Assigns the protection key to the memory mapping. See [`ProtectionKey`] for more
+    /// information.
+    ///
+    /// [`ProtectionKey`]: crate::ProtectionKey
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn with_protection_key(self, key: &crate::ProtectionKey) -> Self {
+        Self {
+            inner: self.inner.with_protection_key(key),
+        }
+    }
+
     /// Reserves inaccessible memory.
     pub fn reserve_none(self) -> Result<ReservedNone, Error> {
         Ok(ReservedNone {
@@ -923,7 +1505,7 @@ pub struct ReservedNone {
 }
 
 reserved_impl!(ReservedNone);
-reserved_mmap_impl!(ReservedNone);
+reserved_mmap_impl!(ReservedNone, Protection::empty());
 
 impl TryFrom<ReservedNone> for MmapNone {
     type Error = Error;
@@ -965,7 +1547,7 @@ pub struct Reserved {
 }
 
 reserved_impl!(Reserved);
-reserved_mmap_impl!(Reserved);
+reserved_mmap_impl!(Reserved, Protection::READ);
 
 impl TryFrom<Reserved> for Mmap {
     type Error = Error;
@@ -987,7 +1569,7 @@ pub struct ReservedMut {
 }
 
 reserved_impl!(ReservedMut);
-reserved_mmap_impl!(ReservedMut);
+reserved_mmap_impl!(ReservedMut, Protection::READ | Protection::WRITE);
 
 impl TryFrom<ReservedMut> for MmapMut {
     type Error = Error;
@@ -0,0 +1,158 @@
+//! This module implements POSIX named shared memory using `shm_open`.
+
+use crate::error::Error;
+use nix::fcntl::OFlag;
+use nix::sys::mman::{shm_open, shm_unlink};
+use nix::sys::stat::Mode;
+use std::fs::File;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+
+/// A POSIX shared memory object that is identified by a name, e.g. `/my-shared-memory`, such that
+/// unrelated processes can open and map the same memory. On Linux, these objects show up in
+/// `/dev/shm`.
+///
+/// The shared memory object persists until it is removed using [`NamedSharedMemory::unlink()`],
+/// even after all processes have closed it. The shared memory can be mapped using
+/// [`MmapOptions::with_shared_memory()`].
+///
+/// [`MmapOptions::with_shared_memory()`]: crate::MmapOptions::with_shared_memory
+#[derive(Debug)]
+pub struct NamedSharedMemory {
+    file: File,
+    name: String,
+}
+
+impl NamedSharedMemory {
+    /// Creates a new shared memory object with the given name and size in bytes. The name should
+    /// start with a slash and contain no other slashes. This fails if a shared memory object with
+    /// the same name already exists.
+    pub fn create(name: &str, size: usize) -> Result<Self, Error> {
+        let fd = shm_open(
+            name,
+            OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_RDWR,
+            Mode::S_IRUSR | Mode::S_IWUSR,
+        )?;
+        let file = File::from(fd);
+
+        if let Err(e) = file.set_len(size as u64) {
+            let _ = shm_unlink(name);
+            return Err(e.into());
+        }
+
+        Ok(Self {
+            file,
+            name: name.to_string(),
+        })
+    }
+
+    /// Creates a new shared memory object of the given size in bytes with a unique name starting
+    /// with the given prefix, and removes it right away, such that only the returned [`File`]
+    /// refers to it.
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn create_unlinked(prefix: &str, size: usize) -> Result<File, Error> {
+        use nix::errno::Errno;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        loop {
+            let name = format!(
+                "/{}-{}-{}",
+                prefix,
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            );
+
+            match Self::create(&name, size) {
+                Ok(shared_memory) => {
+                    Self::unlink(&name)?;
+                    return Ok(shared_memory.into_file());
+                }
+                Err(Error::Nix(Errno::EEXIST)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Opens an existing shared memory object with the given name for reading and writing.
+    pub fn open(name: &str) -> Result<Self, Error> {
+        Self::open_with(name, OFlag::O_RDWR)
+    }
+
+    /// Opens an existing shared memory object with the given name for reading only. The shared
+    /// memory can then only be mapped using [`MmapOptions::map()`] and friends.
+    ///
+    /// [`MmapOptions::map()`]: crate::MmapOptions::map
+    pub fn open_read_only(name: &str) -> Result<Self, Error> {
+        Self::open_with(name, OFlag::O_RDONLY)
+    }
+
+    fn open_with(name: &str, flags: OFlag) -> Result<Self, Error> {
+        let fd = shm_open(name, flags, Mode::empty())?;
+
+        Ok(Self {
+            file: File::from(fd),
+            name: name.to_string(),
+        })
+    }
+
+    /// Removes the shared memory object with the given name. Any processes that have the shared
+    /// memory object open or mapped can continue to use it, but it can no longer be opened.
+    pub fn unlink(name: &str) -> Result<(), Error> {
+        shm_unlink(name)?;
+
+        Ok(())
+    }
+
+    /// Returns the name of the shared memory object.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the size of the shared memory object in bytes.
+    pub fn len(&self) -> Result<u64, Error> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    /// Returns `true` if the shared memory object is empty.
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Returns the [`File`] that refers to the shared memory object.
+    #[inline]
+    pub fn as_file(&self) -> &File {
+        &self.file
+    }
+
+    /// Consumes the shared memory object and returns the [`File`] that refers to it.
+    #[inline]
+    pub fn into_file(self) -> File {
+        self.file
+    }
+}
+
+impl AsRef<File> for NamedSharedMemory {
+    fn as_ref(&self) -> &File {
+        &self.file
+    }
+}
+
+impl AsFd for NamedSharedMemory {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+impl AsRawFd for NamedSharedMemory {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl From<NamedSharedMemory> for OwnedFd {
+    fn from(shared_memory: NamedSharedMemory) -> Self {
+        shared_memory.file.into()
+    }
+}
@@ -0,0 +1,40 @@
+use std::fs::File;
+use std::sync::Arc;
+
+/// The file backing a memory mapping, which is either borrowed for the duration of the
+/// [`MmapOptions`] builder, or owned and kept alive by the resulting memory mapping.
+///
+/// [`MmapOptions`]: crate::MmapOptions
+#[derive(Debug)]
+pub enum MmapFile<'a> {
+    Borrowed(&'a File),
+    Owned(Arc<File>),
+}
+
+impl MmapFile<'_> {
+    /// Returns a reference to the file.
+    pub fn as_file(&self) -> &File {
+        match self {
+            Self::Borrowed(file) => file,
+            Self::Owned(file) => file,
+        }
+    }
+
+    /// Returns the file to store in the memory mapping, if it is owned.
+    pub fn owned(&self) -> Option<Arc<File>> {
+        match self {
+            Self::Borrowed(_) => None,
+            Self::Owned(file) => Some(file.clone()),
+        }
+    }
+}
+
+/// Returns whether both memory mappings are backed by the same owned file, or neither of them
+/// owns a file.
+pub fn same_file(a: &Option<Arc<File>>, b: &Option<Arc<File>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
@@ -1,5 +1,5 @@
 use crate::PageSizes;
-use crate::areas::{MemoryArea, Protection, ShareMode};
+use crate::areas::{MemoryArea, MemoryAreaKind, Protection, ShareMode};
 use crate::error::Error;
 use crate::os_impl::unix::MmapOptions;
 use bitflags::bitflags;
@@ -162,12 +162,23 @@ impl<B: BufRead> Iterator for MemoryAreas<B> {
                 Some((Path::new(path).to_path_buf(), offset))
             };
 
+            let kind = if flags.contains(KvmeFlags::GROWS_DOWN) {
+                MemoryAreaKind::Stack
+            } else if path.is_some() {
+                MemoryAreaKind::File
+            } else {
+                MemoryAreaKind::Anonymous
+            };
+
             return Some(Ok(MemoryArea {
                 allocation_base: start,
                 range: start..end,
                 protection,
                 share_mode,
+                kind,
                 path,
+                device: (0, 0),
+                inode: entry.kve_vn_fileid,
             }));
         }
 
@@ -1,5 +1,5 @@
 use crate::PageSizes;
-use crate::areas::{MemoryArea, Protection, ShareMode};
+use crate::areas::{MemoryArea, MemoryAreaKind, MemoryAreaStats, Protection, ShareMode};
 use crate::error::Error;
 use crate::os_impl::unix::MmapOptions;
 use combine::{
@@ -79,27 +79,75 @@ where
         .map(|(r, w, x, s)| (r | w | x, s))
 }
 
-fn device_id<Input>() -> impl Parser<Input, Output = (u16, u8)>
+fn device_id<Input>() -> impl Parser<Input, Output = (u32, u32)>
 where
     Input: Stream<Token = char>,
     <Input::Error as ParseError<Input::Token, Input::Range, Input::Position>>::StreamError:
         From<::std::num::ParseIntError>,
 {
     (
-        hex_digit1().and_then(|s| u16::from_str_radix(s.as_str(), 16)),
+        hex_digit1().and_then(|s| u32::from_str_radix(s.as_str(), 16)),
         token(':'),
-        hex_digit1().and_then(|s| u8::from_str_radix(s.as_str(), 16)),
+        hex_digit1().and_then(|s| u32::from_str_radix(s.as_str(), 16)),
     )
         .map(|(major, _, minor)| (major, minor))
 }
 
-fn path<Input>() -> impl Parser<Input, Output = PathBuf>
+fn inode<Input>() -> impl Parser<Input, Output = u64>
+where
+    Input: Stream<Token = char>,
+    <Input::Error as ParseError<Input::Token, Input::Range, Input::Position>>::StreamError:
+        From<::std::num::ParseIntError>,
+{
+    many1(digit()).and_then(|s: String| s.parse::<u64>())
+}
+
+fn path<Input>() -> impl Parser<Input, Output = String>
 where
     Input: Stream<Token = char>,
 {
     use combine::parser::token::satisfy;
 
-    many1(satisfy(|c| c != '\n')).map(|s: String| PathBuf::from(s))
+    many1(satisfy(|c| c != '\n'))
+}
+
+/// Determines the kind of the memory area from the path column, which either contains the path to
+/// the file backing the memory area, or a pseudo-path such as `[heap]`. The path to the file is
+/// only returned for memory areas that are backed by a file.
+fn memory_area_kind(path: Option<String>) -> (MemoryAreaKind, Option<PathBuf>) {
+    let path = match path {
+        Some(path) => path,
+        _ => return (MemoryAreaKind::Anonymous, None),
+    };
+
+    if let Some(name) = path
+        .strip_prefix('[')
+        .and_then(|path| path.strip_suffix(']'))
+    {
+        let kind = match name {
+            "heap" => MemoryAreaKind::Heap,
+            "stack" => MemoryAreaKind::Stack,
+            "vdso" => MemoryAreaKind::Vdso,
+            "vvar" | "vvar_vclock" => MemoryAreaKind::Vvar,
+            "vsyscall" => MemoryAreaKind::Vsyscall,
+            // Older kernels list the stacks of threads as [stack:<tid>].
+            _ if name.starts_with("stack:") => MemoryAreaKind::Stack,
+            _ => match name
+                .strip_prefix("anon:")
+                .or_else(|| name.strip_prefix("anon_shmem:"))
+            {
+                Some(name) => MemoryAreaKind::AnonNamed(name.to_string()),
+                _ => MemoryAreaKind::Other(name.to_string()),
+            },
+        };
+
+        return (kind, None);
+    }
+
+    match path.strip_suffix(" (deleted)") {
+        Some(path) => (MemoryAreaKind::Deleted, Some(PathBuf::from(path))),
+        _ => (MemoryAreaKind::File, Some(PathBuf::from(path))),
+    }
 }
 
 fn memory_region<Input>() -> impl Parser<Input, Output = MemoryArea>
@@ -119,21 +167,69 @@ where
         spaces(),
         device_id(),
         spaces(),
-        hex_digit1(),
+        inode(),
         spaces(),
         optional(path()),
     )
         .map(
-            |(range, _, (protection, share_mode), _, offset, _, _, _, _, _, path)| MemoryArea {
-                allocation_base: range.start,
-                range,
-                protection,
-                share_mode,
-                path: path.map(|path| (path, offset)),
+            |(range, _, (protection, share_mode), _, offset, _, device, _, inode, _, path)| {
+                let (kind, path) = memory_area_kind(path);
+
+                MemoryArea {
+                    allocation_base: range.start,
+                    range,
+                    protection,
+                    share_mode,
+                    path: path.map(|path| (path, offset)),
+                    device,
+                    inode,
+                    kind,
+                }
             },
         )
 }
 
+/// Parses a line of `/proc/<pid>/maps` into the memory area it describes.
+pub(crate) fn parse_memory_area(line: &str) -> Option<MemoryArea> {
+    use combine::stream::position::Stream;
+
+    memory_region()
+        .easy_parse(Stream::new(line))
+        .ok()
+        .map(|(region, _)| region)
+}
+
+fn smaps_field<Input>() -> impl Parser<Input, Output = (String, String)>
+where
+    Input: Stream<Token = char>,
+{
+    use combine::parser::{char::spaces, repeat::many, token::satisfy};
+
+    (
+        many1(satisfy(|c: char| c != ':' && !c.is_whitespace())),
+        token(':'),
+        spaces(),
+        many(satisfy(|c| c != '\n')),
+    )
+        .map(|(key, _, _, value)| (key, value))
+}
+
+fn kilobytes<Input>() -> impl Parser<Input, Output = u64>
+where
+    Input: Stream<Token = char>,
+    <Input::Error as ParseError<Input::Token, Input::Range, Input::Position>>::StreamError:
+        From<::std::num::ParseIntError>,
+{
+    use combine::parser::char::spaces;
+
+    (
+        many1(digit()).and_then(|s: String| s.parse::<u64>()),
+        spaces(),
+        string("kB"),
+    )
+        .map(|(size, _, _)| size * 1024)
+}
+
 impl MmapOptions<'_> {
     pub fn page_sizes() -> Result<PageSizes, Error> {
         let mut sizes = 1 << Self::page_size().ilog2();
@@ -196,12 +292,7 @@ impl<B: BufRead> Iterator for MemoryAreas<B> {
                 None => return None,
             };
 
-            use combine::stream::position::Stream;
-
-            let region = match memory_region().easy_parse(Stream::new(line.as_str())) {
-                Ok((region, _)) => region,
-                _ => return None,
-            };
+            let region = parse_memory_area(&line)?;
 
             if let Some(ref range) = self.range {
                 if region.end() <= range.start {
@@ -219,3 +310,98 @@ impl<B: BufRead> Iterator for MemoryAreas<B> {
         None
     }
 }
+
+pub struct DetailedMemoryAreas<B> {
+    lines: Lines<B>,
+    /// The line describing the next memory area, if it has already been read.
+    next_line: Option<String>,
+}
+
+impl DetailedMemoryAreas<BufReader<File>> {
+    pub fn open(pid: Option<u32>) -> Result<Self, Error> {
+        let path = match pid {
+            Some(pid) => format!("/proc/{}/smaps", pid),
+            _ => "/proc/self/smaps".to_string(),
+        };
+
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+        let lines = reader.lines();
+
+        Ok(Self {
+            lines,
+            next_line: None,
+        })
+    }
+}
+
+impl<B: BufRead> Iterator for DetailedMemoryAreas<B> {
+    type Item = Result<(MemoryArea, MemoryAreaStats), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match self.next_line.take().map(Ok).or_else(|| self.lines.next()) {
+            Some(Ok(line)) => line,
+            Some(Err(e)) => return Some(Err(Error::Io(e))),
+            None => return None,
+        };
+
+        use combine::stream::position::Stream;
+
+        let region = parse_memory_area(&line)?;
+
+        let mut stats = MemoryAreaStats::default();
+
+        // The memory area is followed by its fields up to the next memory area.
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(e)) => return Some(Err(Error::Io(e))),
+                None => break,
+            };
+
+            let field = smaps_field()
+                .easy_parse(Stream::new(line.as_str()))
+                .ok()
+                .map(|(field, _)| field);
+
+            let (key, value) = match field {
+                Some(field) => field,
+                _ => {
+                    self.next_line = Some(line);
+                    break;
+                }
+            };
+
+            if key == "VmFlags" {
+                stats.vm_flags = value.split_whitespace().map(String::from).collect();
+                continue;
+            }
+
+            let size = match kilobytes().easy_parse(Stream::new(value.as_str())) {
+                Ok((size, _)) => size,
+                _ => continue,
+            };
+
+            match key.as_str() {
+                "Size" => stats.size = size,
+                "KernelPageSize" => stats.kernel_page_size = size,
+                "MMUPageSize" => stats.mmu_page_size = size,
+                "Rss" => stats.rss = size,
+                "Pss" => stats.pss = size,
+                "Shared_Clean" => stats.shared_clean = size,
+                "Shared_Dirty" => stats.shared_dirty = size,
+                "Private_Clean" => stats.private_clean = size,
+                "Private_Dirty" => stats.private_dirty = size,
+                "Referenced" => stats.referenced = size,
+                "Anonymous" => stats.anonymous = size,
+                "AnonHugePages" => stats.anon_huge_pages = size,
+                "Swap" => stats.swap = size,
+                "SwapPss" => stats.swap_pss = size,
+                "Locked" => stats.locked = size,
+                _ => (),
+            }
+        }
+
+        Some(Ok((region, stats)))
+    }
+}
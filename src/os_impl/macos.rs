@@ -1,4 +1,4 @@
-use crate::areas::{MemoryArea, Protection, ShareMode};
+use crate::areas::{MemoryArea, MemoryAreaKind, Protection, ShareMode};
 use crate::error::Error;
 use libc::proc_regionfilename;
 use mach2::{
@@ -143,7 +143,13 @@ impl<B: BufRead> Iterator for MemoryAreas<B> {
                 range,
                 protection,
                 share_mode,
+                kind: match path {
+                    Some(_) => MemoryAreaKind::File,
+                    _ => MemoryAreaKind::Anonymous,
+                },
                 path,
+                device: (0, 0),
+                inode: 0,
             }));
         }
     }
@@ -1,3 +1,6 @@
+pub mod file;
+pub mod protections;
+
 #[cfg(unix)]
 pub mod unix;
 
@@ -10,5 +13,8 @@ pub mod freebsd;
 #[cfg(any(target_os = "android", target_os = "linux"))]
 pub mod linux;
 
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub mod write_tracker;
+
 #[cfg(any(target_os = "macos", target_os = "ios"))]
 pub mod macos;
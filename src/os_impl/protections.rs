@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+/// Tracks the protection of the pages of a memory mapping. The base protection is the protection
+/// the memory mapping has been mapped with, or the protection last applied to the whole memory
+/// mapping. Any byte ranges that have been given a different protection are tracked as a sorted
+/// list of non-overlapping overrides relative to the start of the memory mapping.
+#[derive(Clone, Debug)]
+pub struct Protections<P> {
+    base: P,
+    overrides: Vec<(Range<usize>, P)>,
+}
+
+impl<P: Copy + Eq> Protections<P> {
+    pub fn new(base: P) -> Self {
+        Self {
+            base,
+            overrides: vec![],
+        }
+    }
+
+    /// Returns the protection the whole memory mapping has been mapped with.
+    #[inline]
+    pub fn base(&self) -> P {
+        self.base
+    }
+
+    /// Returns the protection of the page containing the given offset.
+    pub fn get(&self, offset: usize) -> P {
+        self.overrides
+            .iter()
+            .find(|(range, _)| range.contains(&offset))
+            .map(|(_, protection)| *protection)
+            .unwrap_or(self.base)
+    }
+
+    /// Changes the protection of the whole memory mapping, which discards any overrides.
+    pub fn reset(&mut self, base: P) {
+        self.base = base;
+        self.overrides.clear();
+    }
+
+    /// Changes the protection of the given range.
+    pub fn set(&mut self, range: Range<usize>, protection: P) {
+        let mut overrides = Vec::with_capacity(self.overrides.len() + 2);
+
+        for (other, other_protection) in self.overrides.drain(..) {
+            if other.start < range.start {
+                overrides.push((other.start..other.end.min(range.start), other_protection));
+            }
+
+            if other.end > range.end {
+                overrides.push((other.start.max(range.end)..other.end, other_protection));
+            }
+        }
+
+        if protection != self.base {
+            overrides.push((range, protection));
+        }
+
+        overrides.sort_by_key(|(range, _)| range.start);
+
+        self.overrides = overrides;
+        self.coalesce();
+    }
+
+    /// Splits off the protections at the given offset, such that `self` covers `[0, at)` and the
+    /// returned protections cover `[at, len)`.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let mut left = vec![];
+        let mut right = vec![];
+
+        for (range, protection) in self.overrides.drain(..) {
+            if range.start < at {
+                left.push((range.start..range.end.min(at), protection));
+            }
+
+            if range.end > at {
+                right.push((range.start.max(at) - at..range.end - at, protection));
+            }
+        }
+
+        self.overrides = left;
+
+        Self {
+            base: self.base,
+            overrides: right,
+        }
+    }
+
+    /// Splits off the protections at the given offset, such that `self` covers `[at, len)` and
+    /// the returned protections cover `[0, at)`.
+    pub fn split_to(&mut self, at: usize) -> Self {
+        let right = self.split_off(at);
+
+        std::mem::replace(self, right)
+    }
+
+    /// Appends the protections of another memory mapping that starts at the given offset.
+    pub fn append(&mut self, offset: usize, other: &Self) {
+        self.overrides.extend(
+            other
+                .overrides
+                .iter()
+                .map(|(range, protection)| (range.start + offset..range.end + offset, *protection)),
+        );
+
+        self.coalesce();
+    }
+
+    /// Updates the protections after the memory mapping has been resized. When growing the memory
+    /// mapping, the new pages inherit the protection of the last page.
+    pub fn resize(&mut self, old_size: usize, new_size: usize) {
+        if new_size < old_size {
+            self.overrides.retain_mut(|(range, _)| {
+                range.end = range.end.min(new_size);
+                range.start < new_size
+            });
+        } else if let Some((range, _)) = self.overrides.last_mut() {
+            if range.end == old_size {
+                range.end = new_size;
+            }
+        }
+    }
+
+    fn coalesce(&mut self) {
+        let mut overrides: Vec<(Range<usize>, P)> = Vec::with_capacity(self.overrides.len());
+
+        for (range, protection) in self.overrides.drain(..) {
+            match overrides.last_mut() {
+                Some((last, last_protection))
+                    if last.end == range.start && *last_protection == protection =>
+                {
+                    last.end = range.end;
+                }
+                _ => overrides.push((range, protection)),
+            }
+        }
+
+        self.overrides = overrides;
+    }
+}
@@ -1,5 +1,8 @@
+use crate::areas::Protection;
 use crate::error::Error;
-use crate::{MmapFlags, PageSize, UnsafeMmapFlags};
+use crate::os_impl::file::{MmapFile, same_file};
+use crate::os_impl::protections::Protections;
+use crate::{Advice, MmapFlags, PageSize, RemapPolicy, UnsafeMmapFlags};
 use bitflags::bitflags;
 use nix::sys::mman::*;
 use nix::unistd::*;
@@ -7,10 +10,58 @@ use std::fs::File;
 use std::num::NonZeroUsize;
 use std::ops::Range;
 use std::ptr::NonNull;
+use std::sync::Arc;
 
 #[cfg(not(any(target_os = "android", target_os = "freebsd", target_os = "linux")))]
 use crate::PageSizes;
 
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use crate::protection_key::{ProtectionKey, pkey_mprotect};
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+use crate::os_impl::write_tracker;
+
+/// The number of the `mseal()` system call, as `libc` does not define it for every target. It is
+/// the same on all architectures, except for those that offset their system call numbers.
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    any(target_arch = "mips", target_arch = "mips32r6")
+))]
+const SYS_MSEAL: libc::c_long = 4000 + 462;
+
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    any(target_arch = "mips64", target_arch = "mips64r6"),
+    target_pointer_width = "64"
+))]
+const SYS_MSEAL: libc::c_long = 5000 + 462;
+
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    any(target_arch = "mips64", target_arch = "mips64r6"),
+    target_pointer_width = "32"
+))]
+const SYS_MSEAL: libc::c_long = 6000 + 462;
+
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    target_arch = "x86_64",
+    target_pointer_width = "32"
+))]
+const SYS_MSEAL: libc::c_long = 0x4000_0000 + 462;
+
+#[cfg(all(
+    any(target_os = "android", target_os = "linux"),
+    not(any(
+        target_arch = "mips",
+        target_arch = "mips32r6",
+        target_arch = "mips64",
+        target_arch = "mips64r6",
+        all(target_arch = "x86_64", target_pointer_width = "32")
+    ))
+))]
+const SYS_MSEAL: libc::c_long = 462;
+
 #[cfg(target_os = "ios")]
 unsafe extern "C" {
     fn sys_icache_invalidate(start: *mut core::ffi::c_void, size: usize);
@@ -25,7 +76,8 @@ unsafe extern "C" {
 bitflags! {
     #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
     struct Flags: u32 {
-        const JIT = 1 << 0;
+        const JIT    = 1 << 0;
+        const SEALED = 1 << 1;
     }
 }
 
@@ -33,7 +85,15 @@ bitflags! {
 pub struct Mmap {
     ptr: NonNull<std::ffi::c_void>,
     size: usize,
+    /// The number of bytes mapped in front of `ptr` that are hidden from the user, e.g. as the
+    /// file offset had to be aligned down to the page size.
+    offset: usize,
     flags: Flags,
+    map_flags: MapFlags,
+    protections: Protections<ProtFlags>,
+    guard_before: usize,
+    guard_after: usize,
+    file: Option<Arc<File>>,
 }
 
 unsafe impl Send for Mmap {}
@@ -55,9 +115,28 @@ impl Mmap {
         self.size
     }
 
+    #[inline]
+    pub fn file(&self) -> Option<&File> {
+        self.file.as_deref()
+    }
+
+    /// Returns the start of the underlying memory mapping, including any hidden leading bytes.
+    #[inline]
+    fn base(&self) -> NonNull<std::ffi::c_void> {
+        unsafe { self.ptr.sub(self.offset) }
+    }
+
+    /// Returns the start of the given byte range relative to the start of the underlying memory
+    /// mapping. A range that starts at the start of the memory mapping also covers any hidden
+    /// leading bytes, such that it starts at a page boundary.
+    #[inline]
+    fn real_start(&self, start: usize) -> usize {
+        if start == 0 { 0 } else { self.offset + start }
+    }
+
     pub fn lock(&mut self) -> Result<(), Error> {
         unsafe {
-            mlock(self.ptr, self.size)?;
+            mlock(self.base(), self.offset + self.size)?;
         }
 
         Ok(())
@@ -65,29 +144,41 @@ impl Mmap {
 
     pub fn unlock(&mut self) -> Result<(), Error> {
         unsafe {
-            munlock(self.ptr, self.size)?;
+            munlock(self.base(), self.offset + self.size)?;
         }
 
         Ok(())
     }
 
     pub fn flush(&self, range: Range<usize>) -> Result<(), Error> {
+        // msync() requires the address to be page aligned.
+        let start = self.offset + range.start;
+        let start = start - start % MmapOptions::page_size();
+
         unsafe {
             msync(
-                self.ptr.add(range.start),
-                range.end - range.start,
+                self.base().add(start),
+                self.offset + range.end - start,
                 MsFlags::MS_SYNC,
             )
         }?;
 
+        if let Some(file) = &self.file {
+            file.sync_data()?;
+        }
+
         Ok(())
     }
 
     pub fn flush_async(&self, range: Range<usize>) -> Result<(), Error> {
+        // msync() requires the address to be page aligned.
+        let start = self.offset + range.start;
+        let start = start - start % MmapOptions::page_size();
+
         unsafe {
             msync(
-                self.ptr.add(range.start),
-                range.end - range.start,
+                self.base().add(start),
+                self.offset + range.end - start,
                 MsFlags::MS_ASYNC,
             )
         }?;
@@ -109,17 +200,365 @@ impl Mmap {
         Ok(())
     }
 
+    pub fn advise(&mut self, range: Range<usize>, advice: Advice) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        let start = self.real_start(range.start);
+
+        if start % MmapOptions::page_size() != 0 {
+            return Err(Error::InvalidOffset);
+        }
+
+        let advise = match advice {
+            Advice::Normal => MmapAdvise::MADV_NORMAL,
+            Advice::Random => MmapAdvise::MADV_RANDOM,
+            Advice::Sequential => MmapAdvise::MADV_SEQUENTIAL,
+            Advice::WillNeed => MmapAdvise::MADV_WILLNEED,
+            Advice::DontNeed => MmapAdvise::MADV_DONTNEED,
+            Advice::Free => MmapAdvise::MADV_FREE,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::Cold => MmapAdvise::MADV_COLD,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::PageOut => MmapAdvise::MADV_PAGEOUT,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::Remove => MmapAdvise::MADV_REMOVE,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::HugePage => MmapAdvise::MADV_HUGEPAGE,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::NoHugePage => MmapAdvise::MADV_NOHUGEPAGE,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::DontFork => MmapAdvise::MADV_DONTFORK,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::DoFork => MmapAdvise::MADV_DOFORK,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::WipeOnFork => MmapAdvise::MADV_WIPEONFORK,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::KeepOnFork => MmapAdvise::MADV_KEEPONFORK,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::DontDump => MmapAdvise::MADV_DONTDUMP,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::DoDump => MmapAdvise::MADV_DODUMP,
+            #[cfg(any(target_os = "dragonfly", target_os = "freebsd"))]
+            Advice::DontDump => MmapAdvise::MADV_NOCORE,
+            #[cfg(any(target_os = "dragonfly", target_os = "freebsd"))]
+            Advice::DoDump => MmapAdvise::MADV_CORE,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::Mergeable => MmapAdvise::MADV_MERGEABLE,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::Unmergeable => MmapAdvise::MADV_UNMERGEABLE,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::PopulateRead => MmapAdvise::MADV_POPULATE_READ,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            Advice::PopulateWrite => MmapAdvise::MADV_POPULATE_WRITE,
+            #[allow(unreachable_patterns)]
+            _ => return Err(Error::UnsupportedAdvice(advice)),
+        };
+
+        unsafe {
+            madvise(
+                self.base().add(start),
+                self.offset + range.end - start,
+                advise,
+            )
+        }?;
+
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn set_name(&self, name: Option<&str>) -> Result<(), Error> {
+        // Reject the same names as the kernel, which guarantees that the name contains no NUL.
+        let name = match name {
+            Some(name) => {
+                let valid = name.len() < 80
+                    && name
+                        .bytes()
+                        .all(|b| (0x20..0x7f).contains(&b) && !b"\\`$[]".contains(&b));
+
+                if !valid {
+                    return Err(Error::Nix(nix::errno::Errno::EINVAL));
+                }
+
+                Some(std::ffi::CString::new(name).unwrap())
+            }
+            _ => None,
+        };
+
+        let size = (self.offset + self.size).next_multiple_of(MmapOptions::page_size());
+
+        let status = unsafe {
+            libc::prctl(
+                libc::PR_SET_VMA,
+                libc::PR_SET_VMA_ANON_NAME as libc::c_ulong,
+                self.base().as_ptr() as libc::c_ulong,
+                size as libc::c_ulong,
+                name.as_ref().map_or(std::ptr::null(), |name| name.as_ptr()),
+            )
+        };
+
+        // The name has been validated above, so the kernel does not support naming memory
+        // mappings.
+        match nix::errno::Errno::result(status) {
+            Ok(_) => (),
+            Err(nix::errno::Errno::EINVAL) => return Err(Error::InvalidOperation),
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(())
+    }
+
+    pub fn residency(&self, range: Range<usize>) -> Result<Vec<bool>, Error> {
+        if range.start > range.end || range.end > self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        if range.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // mincore() requires the address to be page aligned.
+        let page_size = MmapOptions::page_size();
+        let start = self.offset + range.start;
+        let start = start - start % page_size;
+        let size = self.offset + range.end - start;
+
+        let mut pages = vec![0u8; size.div_ceil(page_size)];
+
+        let status = unsafe {
+            libc::mincore(
+                self.base().add(start).as_ptr() as _,
+                size,
+                pages.as_mut_ptr() as _,
+            )
+        };
+
+        nix::errno::Errno::result(status)?;
+
+        Ok(pages.into_iter().map(|page| page & 1 != 0).collect())
+    }
+
     fn do_make(&mut self, protect: ProtFlags) -> Result<(), Error> {
-        let ptr = self.ptr;
-        let size = self.size;
+        if self.flags.contains(Flags::SEALED) {
+            return Err(Error::Sealed);
+        }
+
+        self.stop_tracking_writes();
+
+        let ptr = self.base();
+        let size = self.offset + self.size;
 
         unsafe {
             mprotect(ptr, size, protect)?;
         }
 
+        self.protections.reset(protect);
+
         Ok(())
     }
 
+    pub fn protect_range(
+        &mut self,
+        range: Range<usize>,
+        protection: Protection,
+    ) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        if self.flags.contains(Flags::SEALED) {
+            return Err(Error::Sealed);
+        }
+
+        let page_size = MmapOptions::page_size();
+        let start = self.real_start(range.start);
+        let end = self.offset + range.end;
+
+        if start % page_size != 0 || (end % page_size != 0 && range.end != self.size) {
+            return Err(Error::InvalidOffset);
+        }
+
+        if protection.contains(Protection::WRITE | Protection::EXECUTE)
+            && !self.flags.contains(Flags::JIT)
+        {
+            return Err(Error::UnsafeFlagNeeded(UnsafeMmapFlags::JIT));
+        }
+
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let mut protect = ProtFlags::PROT_NONE;
+
+        if protection.contains(Protection::READ) {
+            protect |= ProtFlags::PROT_READ;
+        }
+
+        if protection.contains(Protection::WRITE) {
+            protect |= ProtFlags::PROT_WRITE;
+        }
+
+        if protection.contains(Protection::EXECUTE) {
+            protect |= ProtFlags::PROT_EXEC;
+        }
+
+        self.stop_tracking_writes();
+
+        unsafe {
+            mprotect(self.base().add(start), end - start, protect)?;
+        }
+
+        self.protections.set(range, protect);
+
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn assign_key(&mut self, range: Range<usize>, key: &ProtectionKey) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        if self.flags.contains(Flags::SEALED) {
+            return Err(Error::Sealed);
+        }
+
+        let page_size = MmapOptions::page_size();
+        let start = self.real_start(range.start);
+        let end = self.offset + range.end;
+
+        if start % page_size != 0 || (end % page_size != 0 && range.end != self.size) {
+            return Err(Error::InvalidOffset);
+        }
+
+        let end = end.next_multiple_of(page_size);
+
+        self.stop_tracking_writes();
+
+        // The protection has to be passed along with the key, so assign the key to every run of
+        // pages that share the same protection.
+        let mut run = start;
+
+        while run < end {
+            let protect = self.protections.get(run.saturating_sub(self.offset));
+            let mut run_end = run + page_size;
+
+            while run_end < end && self.protections.get(run_end - self.offset) == protect {
+                run_end += page_size;
+            }
+
+            unsafe {
+                pkey_mprotect(
+                    self.base().add(run),
+                    run_end - run,
+                    protect,
+                    key.key() as libc::c_int,
+                )
+            }?;
+
+            run = run_end;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn seal(&mut self) -> Result<(), Error> {
+        self.stop_tracking_writes();
+
+        // Seal the guard pages as well, such that they cannot be made accessible.
+        let size = self.guard_before
+            + (self.offset + self.size).next_multiple_of(MmapOptions::page_size())
+            + self.guard_after;
+
+        let status = unsafe {
+            libc::syscall(
+                SYS_MSEAL,
+                self.base().as_ptr().byte_sub(self.guard_before),
+                size,
+                0,
+            )
+        };
+
+        match nix::errno::Errno::result(status) {
+            Ok(_) => (),
+            Err(nix::errno::Errno::ENOSYS) => return Err(Error::InvalidOperation),
+            Err(e) => return Err(e.into()),
+        }
+
+        self.flags |= Flags::SEALED;
+
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    #[inline]
+    pub fn is_sealed(&self) -> bool {
+        self.flags.contains(Flags::SEALED)
+    }
+
+    /// Starts tracking the writes to the pages by write-protecting them, or forgets about the
+    /// pages that have been written to if the writes are already being tracked.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn track_writes(&mut self) -> Result<(), Error> {
+        if self.flags.contains(Flags::SEALED) {
+            return Err(Error::Sealed);
+        }
+
+        let page_size = MmapOptions::page_size();
+        let pages = (self.offset + self.size).div_ceil(page_size);
+        let protections = (0..pages)
+            .map(|page| {
+                self.protections
+                    .get((page * page_size).saturating_sub(self.offset))
+            })
+            .collect();
+
+        // The tracking stops before the pages are unmapped or their protection changes.
+        unsafe { write_tracker::track(self.base(), protections) }
+    }
+
+    /// Returns whether the page containing the given address has been written to since the
+    /// writes are being tracked. All pages are considered to be written to if the writes are not
+    /// being tracked.
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn is_written(&self, address: usize) -> bool {
+        let page = (address - self.base().as_ptr() as usize) / MmapOptions::page_size();
+
+        write_tracker::is_written(self.base(), page)
+    }
+
+    /// Stops tracking the writes to the pages, which restores their protection. This has to be
+    /// done before the pages are remapped or unmapped, or before their protection changes.
+    fn stop_tracking_writes(&mut self) {
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        write_tracker::untrack(self.base());
+    }
+
+    pub fn protection_at(&self, offset: usize) -> Result<Protection, Error> {
+        if offset >= self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        let protect = self.protections.get(offset);
+        let mut protection = Protection::empty();
+
+        if protect.contains(ProtFlags::PROT_READ) {
+            protection |= Protection::READ;
+        }
+
+        if protect.contains(ProtFlags::PROT_WRITE) {
+            protection |= Protection::WRITE;
+        }
+
+        if protect.contains(ProtFlags::PROT_EXEC) {
+            protection |= Protection::EXECUTE;
+        }
+
+        Ok(protection)
+    }
+
     pub fn make_none(&mut self) -> Result<(), Error> {
         self.do_make(ProtFlags::PROT_NONE)
     }
@@ -148,25 +587,154 @@ impl Mmap {
         Ok(())
     }
 
-    pub fn merge(&mut self, other: &Self) -> Result<(), Error> {
+    #[cfg(target_os = "linux")]
+    pub fn resize(&mut self, new_size: usize, policy: RemapPolicy) -> Result<(), Error> {
+        if new_size == 0 {
+            return Err(Error::InvalidSize);
+        }
+
+        if self.flags.contains(Flags::SEALED) {
+            return Err(Error::Sealed);
+        }
+
+        if self.guard_before != 0 || self.guard_after != 0 {
+            return Err(Error::InvalidOperation);
+        }
+
+        // The pages beyond the end of the file would raise SIGBUS when accessed.
+        if new_size > self.size && !self.map_flags.contains(MapFlags::MAP_ANONYMOUS) {
+            return Err(Error::InvalidOperation);
+        }
+
+        self.stop_tracking_writes();
+
+        let flags = match policy {
+            RemapPolicy::MayMove => MRemapFlags::MREMAP_MAYMOVE,
+            RemapPolicy::InPlaceOnly => MRemapFlags::empty(),
+        };
+
+        let ptr = unsafe {
+            mremap(
+                self.base(),
+                self.offset + self.size,
+                self.offset + new_size,
+                flags,
+                None,
+            )
+        }?;
+
+        self.ptr = unsafe { ptr.add(self.offset) };
+        self.protections.resize(self.size, new_size);
+        self.size = new_size;
+
+        Ok(())
+    }
+
+    /// Platforms without `mremap()` can only shrink the memory mapping or grow it in place by
+    /// mapping the pages that directly follow it, which is only possible for anonymous memory
+    /// mappings. As the memory mapping is never moved, the policy does not matter.
+    #[cfg(not(target_os = "linux"))]
+    pub fn resize(&mut self, new_size: usize, _policy: RemapPolicy) -> Result<(), Error> {
+        if new_size == 0 {
+            return Err(Error::InvalidSize);
+        }
+
+        if self.guard_before != 0 || self.guard_after != 0 {
+            return Err(Error::InvalidOperation);
+        }
+
+        self.stop_tracking_writes();
+
+        let page_size = MmapOptions::page_size();
+        let old_end = (self.offset + self.size).next_multiple_of(page_size);
+        let new_end = (self.offset + new_size).next_multiple_of(page_size);
+
+        if new_end < old_end {
+            unsafe { munmap(self.base().add(new_end), old_end - new_end) }?;
+        } else if new_end > old_end {
+            if !self.map_flags.contains(MapFlags::MAP_ANONYMOUS) {
+                return Err(Error::InvalidOperation);
+            }
+
+            let address = self.base().as_ptr() as usize + old_end;
+
+            let ptr = unsafe {
+                mmap_anonymous(
+                    NonZeroUsize::new(address),
+                    NonZeroUsize::new(new_end - old_end).ok_or(Error::InvalidSize)?,
+                    self.protections.get(self.size - 1),
+                    // The address is only a hint, such that any memory mapping that follows is
+                    // never replaced.
+                    self.map_flags - MapFlags::MAP_FIXED,
+                )
+            }?;
+
+            if ptr.as_ptr() as usize != address {
+                let _ = unsafe { munmap(ptr, new_end - old_end) };
+
+                return Err(Error::InvalidOperation);
+            }
+        }
+
+        self.protections.resize(self.size, new_size);
+        self.size = new_size;
+
+        Ok(())
+    }
+
+    pub fn merge(&mut self, other: &mut Self) -> Result<(), Error> {
+        if self.flags.contains(Flags::SEALED) || other.flags.contains(Flags::SEALED) {
+            return Err(Error::Sealed);
+        }
+
+        if !same_file(&self.file, &other.file) {
+            return Err(Error::BackingMismatch);
+        }
+
+        // The hidden leading bytes of the other memory mapping would overlap with this one.
+        if other.offset != 0 {
+            return Err(Error::MustBeAdjacent);
+        }
+
+        // The guard pages in between would end up inside the merged memory mapping.
+        if self.guard_after != 0 || other.guard_before != 0 {
+            return Err(Error::MustBeAdjacent);
+        }
+
         if self.flags != other.flags {
             return Err(Error::AttributeMismatch);
         }
 
+        if self.protections.base() != other.protections.base() {
+            return Err(Error::AttributeMismatch);
+        }
+
+        self.stop_tracking_writes();
+        other.stop_tracking_writes();
+
+        self.protections.append(self.size, &other.protections);
         self.size += other.size;
+        self.guard_after = other.guard_after;
+        other.file = None;
 
         Ok(())
     }
 
     pub fn split_off(&mut self, at: usize) -> Result<Self, Error> {
+        if self.flags.contains(Flags::SEALED) {
+            return Err(Error::Sealed);
+        }
+
         if at >= self.size {
             return Err(Error::InvalidOffset);
         }
 
-        if at % MmapOptions::page_size() != 0 {
+        if (self.offset + at) % MmapOptions::page_size() != 0 {
             return Err(Error::InvalidOffset);
         }
 
+        self.stop_tracking_writes();
+
         let ptr = unsafe { self.ptr.add(at) };
         let size = self.size - at;
         self.size = at;
@@ -174,19 +742,31 @@ impl Mmap {
         Ok(Self {
             ptr,
             size,
+            offset: 0,
             flags: self.flags,
+            map_flags: self.map_flags,
+            protections: self.protections.split_off(at),
+            guard_before: 0,
+            guard_after: std::mem::take(&mut self.guard_after),
+            file: self.file.clone(),
         })
     }
 
     pub fn split_to(&mut self, at: usize) -> Result<Self, Error> {
+        if self.flags.contains(Flags::SEALED) {
+            return Err(Error::Sealed);
+        }
+
         if at >= self.size {
             return Err(Error::InvalidOffset);
         }
 
-        if at % MmapOptions::page_size() != 0 {
+        if (self.offset + at) % MmapOptions::page_size() != 0 {
             return Err(Error::InvalidOffset);
         }
 
+        self.stop_tracking_writes();
+
         let ptr = self.ptr;
         self.ptr = unsafe { self.ptr.add(at) };
         let size = at;
@@ -195,36 +775,68 @@ impl Mmap {
         Ok(Self {
             ptr,
             size,
+            offset: std::mem::take(&mut self.offset),
             flags: self.flags,
+            map_flags: self.map_flags,
+            protections: self.protections.split_to(at),
+            guard_before: std::mem::take(&mut self.guard_before),
+            guard_after: 0,
+            file: self.file.clone(),
         })
     }
 }
 
 impl Drop for Mmap {
     fn drop(&mut self) {
-        let _ = unsafe { munmap(self.ptr, self.size) };
+        self.stop_tracking_writes();
+
+        if self.guard_before == 0 && self.guard_after == 0 {
+            let _ = unsafe { munmap(self.base(), self.offset + self.size) };
+            return;
+        }
+
+        // Unmap the guard pages together with the memory mapping.
+        let size = self.guard_before
+            + (self.offset + self.size).next_multiple_of(MmapOptions::page_size())
+            + self.guard_after;
+
+        let _ = unsafe { munmap(self.base().sub(self.guard_before), size) };
     }
 }
 
 #[derive(Debug)]
 pub struct MmapOptions<'a> {
     address: Option<usize>,
-    file: Option<(&'a File, u64)>,
+    exact_address: bool,
+    file: Option<(MmapFile<'a>, u64)>,
     size: NonZeroUsize,
     flags: MmapFlags,
     unsafe_flags: UnsafeMmapFlags,
     page_size: Option<PageSize>,
+    guard_pages: (usize, usize),
+    alignment: Option<usize>,
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    name: Option<String>,
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    protection_key: Option<libc::c_int>,
 }
 
 impl<'a> MmapOptions<'a> {
     pub fn new(size: usize) -> Result<Self, Error> {
         Ok(Self {
             address: None,
+            exact_address: false,
             file: None,
             size: NonZeroUsize::new(size).ok_or(Error::InvalidSize)?,
             flags: MmapFlags::empty(),
             unsafe_flags: UnsafeMmapFlags::empty(),
             page_size: None,
+            guard_pages: (0, 0),
+            alignment: None,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            name: None,
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            protection_key: None,
         })
     }
 
@@ -253,8 +865,19 @@ impl<'a> MmapOptions<'a> {
         self
     }
 
+    pub fn with_exact_address(mut self, address: usize) -> Self {
+        self.address = Some(address);
+        self.exact_address = true;
+        self
+    }
+
     pub fn with_file(mut self, file: &'a File, offset: u64) -> Self {
-        self.file = Some((file, offset));
+        self.file = Some((MmapFile::Borrowed(file), offset));
+        self
+    }
+
+    pub fn with_owned_file(mut self, file: Arc<File>, offset: u64) -> Self {
+        self.file = Some((MmapFile::Owned(file), offset));
         self
     }
 
@@ -273,6 +896,28 @@ impl<'a> MmapOptions<'a> {
         self
     }
 
+    pub fn with_guard_pages(mut self, before: usize, after: usize) -> Self {
+        self.guard_pages = (before, after);
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    pub fn with_protection_key(mut self, key: &ProtectionKey) -> Self {
+        self.protection_key = Some(key.key() as libc::c_int);
+        self
+    }
+
     fn flags(&self) -> MapFlags {
         let mut flags = MapFlags::empty();
 
@@ -357,6 +1002,11 @@ impl<'a> MmapOptions<'a> {
             flags |= MapFlags::MAP_FIXED;
         }
 
+        #[cfg(target_os = "linux")]
+        if self.exact_address {
+            flags |= MapFlags::MAP_FIXED_NOREPLACE;
+        }
+
         #[cfg(any(target_os = "ios", target_os = "macos"))]
         if self.unsafe_flags.contains(UnsafeMmapFlags::JIT) {
             flags |= MapFlags::MAP_JIT;
@@ -365,29 +1015,179 @@ impl<'a> MmapOptions<'a> {
         flags
     }
 
+    /// Verifies that the memory has been mapped at the exact address, if one has been requested.
+    /// Without `MAP_FIXED_NOREPLACE`, the address is only a hint, such that the memory mapping is
+    /// unmapped again if it has been mapped elsewhere.
+    fn verify_exact_address(
+        &self,
+        result: nix::Result<NonNull<std::ffi::c_void>>,
+        address: Option<NonZeroUsize>,
+        size: usize,
+    ) -> Result<NonNull<std::ffi::c_void>, Error> {
+        if !self.exact_address {
+            return Ok(result?);
+        }
+
+        let ptr = match result {
+            Err(nix::errno::Errno::EEXIST) => return Err(Error::AddressInUse),
+            result => result?,
+        };
+
+        if address.map(NonZeroUsize::get) != Some(ptr.as_ptr() as usize) {
+            let _ = unsafe { munmap(ptr, size) };
+
+            return Err(Error::AddressInUse);
+        }
+
+        Ok(ptr)
+    }
+
+    /// Reserves the address range for the memory mapping and the guard pages surrounding it as
+    /// inaccessible memory. Returns the start and size of the reservation, if there are any guard
+    /// pages or if the memory mapping has to be aligned.
+    fn reserve_range(
+        &self,
+        size: NonZeroUsize,
+    ) -> Result<Option<(NonNull<std::ffi::c_void>, usize)>, Error> {
+        let page_size = Self::page_size();
+        let (before, after) = self.guard_pages;
+
+        // Only over-reserve if the kernel does not already guarantee the alignment.
+        let alignment = self
+            .alignment
+            .filter(|&alignment| alignment > page_size && self.address.is_none());
+
+        if before == 0 && after == 0 && alignment.is_none() {
+            return Ok(None);
+        }
+
+        let guard_before = before.checked_mul(page_size).ok_or(Error::InvalidSize)?;
+        let guard_after = after.checked_mul(page_size).ok_or(Error::InvalidSize)?;
+        let size = guard_before
+            .checked_add(size.get().next_multiple_of(page_size))
+            .and_then(|size| size.checked_add(guard_after))
+            .and_then(NonZeroUsize::new)
+            .ok_or(Error::InvalidSize)?;
+
+        let address = match self.address {
+            Some(address) => Some(
+                address
+                    .checked_sub(guard_before)
+                    .and_then(NonZeroUsize::new)
+                    .ok_or(Error::InvalidOffset)?,
+            ),
+            _ => None,
+        };
+
+        let mut flags = MapFlags::MAP_PRIVATE;
+
+        if self.unsafe_flags.contains(UnsafeMmapFlags::MAP_FIXED) {
+            flags |= MapFlags::MAP_FIXED;
+        }
+
+        #[cfg(target_os = "linux")]
+        if self.exact_address {
+            flags |= MapFlags::MAP_FIXED_NOREPLACE;
+        }
+
+        let Some(alignment) = alignment else {
+            let ptr = unsafe { mmap_anonymous(address, size, ProtFlags::PROT_NONE, flags) };
+            let ptr = self.verify_exact_address(ptr, address, size.get())?;
+
+            return Ok(Some((ptr, size.get())));
+        };
+
+        // Reserve enough memory to find an aligned start within and trim the excess on both ends.
+        let padding = alignment - page_size;
+        let padded_size = size.checked_add(padding).ok_or(Error::InvalidSize)?;
+        let ptr = unsafe {
+            mmap_anonymous(
+                None,
+                padded_size,
+                ProtFlags::PROT_NONE,
+                MapFlags::MAP_PRIVATE,
+            )
+        }?;
+
+        let start = ptr.as_ptr() as usize;
+        let head = (start + guard_before).next_multiple_of(alignment) - guard_before - start;
+        let tail = padding - head;
+
+        if head != 0 {
+            unsafe { munmap(ptr, head) }?;
+        }
+
+        let ptr = unsafe { ptr.byte_add(head) };
+
+        if tail != 0 {
+            unsafe { munmap(ptr.byte_add(size.get()), tail) }?;
+        }
+
+        Ok(Some((ptr, size.get())))
+    }
+
     fn do_map(self, protect: ProtFlags) -> Result<Mmap, Error> {
-        let size = self.size;
-        let ptr = if let Some((file, offset)) = self.file {
-            unsafe {
-                mmap(
-                    self.address.and_then(NonZeroUsize::new),
-                    size,
-                    protect,
-                    self.flags(),
-                    file,
-                    offset.try_into()?,
-                )
+        if let Some(alignment) = self.alignment {
+            if !alignment.is_power_of_two() {
+                return Err(Error::InvalidAlignment);
             }
+
+            if self.address.is_some_and(|address| address % alignment != 0) {
+                return Err(Error::InvalidAlignment);
+            }
+        }
+
+        let mut address = self.address.and_then(NonZeroUsize::new);
+        let mut map_flags = self.flags();
+
+        // The file offset must be page aligned. Align it down and hide the leading bytes.
+        let (offset, skip) = match &self.file {
+            Some((_, offset)) => {
+                let skip = offset % Self::allocation_granularity() as u64;
+
+                ((offset - skip).try_into()?, skip as usize)
+            }
+            _ => (0, 0),
+        };
+
+        let size = self.size.checked_add(skip).ok_or(Error::InvalidSize)?;
+
+        // If there are any guard pages, then map the memory mapping in between them. This is also
+        // used to align the memory mapping.
+        let reservation = self.reserve_range(size)?;
+        let guard_before = self.guard_pages.0 * Self::page_size();
+        let guard_after = self.guard_pages.1 * Self::page_size();
+
+        if let Some((ptr, _)) = reservation {
+            address = NonZeroUsize::new(ptr.as_ptr() as usize + guard_before);
+            map_flags |= MapFlags::MAP_FIXED;
+
+            // The address range has already been reserved, so it must be replaced.
+            #[cfg(target_os = "linux")]
+            map_flags.remove(MapFlags::MAP_FIXED_NOREPLACE);
+        }
+
+        let ptr = if let Some((file, _)) = &self.file {
+            unsafe { mmap(address, size, protect, map_flags, file.as_file(), offset) }
         } else {
-            unsafe {
-                mmap_anonymous(
-                    self.address.and_then(NonZeroUsize::new),
-                    size,
-                    protect,
-                    self.flags(),
-                )
+            unsafe { mmap_anonymous(address, size, protect, map_flags) }
+        };
+
+        let ptr = match reservation {
+            Some(_) => ptr.map_err(Error::from),
+            _ => self.verify_exact_address(ptr, address, size.get()),
+        };
+
+        let ptr = match ptr {
+            Ok(ptr) => ptr,
+            Err(e) => {
+                if let Some((ptr, size)) = reservation {
+                    let _ = unsafe { munmap(ptr, size) };
+                }
+
+                return Err(e);
             }
-        }?;
+        };
 
         #[cfg(any(target_os = "android", target_os = "linux"))]
         if self.flags.contains(MmapFlags::NO_CORE_DUMP) {
@@ -435,11 +1235,34 @@ impl<'a> MmapOptions<'a> {
             flags |= Flags::JIT;
         }
 
-        Ok(Mmap {
-            ptr,
-            size: size.get(),
+        #[cfg(target_os = "linux")]
+        map_flags.remove(MapFlags::MAP_FIXED_NOREPLACE);
+
+        let mmap = Mmap {
+            ptr: unsafe { ptr.add(skip) },
+            size: self.size.get(),
+            offset: skip,
             flags,
-        })
+            map_flags: map_flags - MapFlags::MAP_FIXED,
+            protections: Protections::new(protect),
+            guard_before,
+            guard_after,
+            file: self.file.as_ref().and_then(|(file, _)| file.owned()),
+        };
+
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        if let Some(name) = &self.name {
+            mmap.set_name(Some(name))?;
+        }
+
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        if let Some(key) = self.protection_key {
+            let size = (mmap.offset + mmap.size).next_multiple_of(Self::page_size());
+
+            unsafe { pkey_mprotect(mmap.base(), size, protect, key) }?;
+        }
+
+        Ok(mmap)
     }
 
     pub fn reserve_none(self) -> Result<Mmap, Error> {
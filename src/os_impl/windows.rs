@@ -1,17 +1,21 @@
-use crate::areas::{MemoryArea, Protection, ShareMode};
+use crate::areas::{MemoryArea, MemoryAreaKind, Protection, ShareMode};
 use crate::error::Error;
-use crate::mmap::{MmapFlags, PageSize, PageSizes, UnsafeMmapFlags};
+use crate::mmap::{Advice, MmapFlags, PageSize, PageSizes, RemapPolicy, UnsafeMmapFlags};
+use crate::os_impl::file::{MmapFile, same_file};
+use crate::os_impl::protections::Protections;
 use bitflags::bitflags;
 use std::fs::File;
 use std::ops::Range;
 use std::os::windows::io::AsRawHandle;
 use std::path::PathBuf;
 use std::sync::Arc;
-use windows::Win32::Foundation::{CloseHandle, HANDLE, MAX_PATH};
+use windows::Win32::Foundation::{CloseHandle, ERROR_INVALID_ADDRESS, HANDLE, MAX_PATH};
 #[cfg(any(target_arch = "arm", target_arch = "aarch64"))]
 use windows::Win32::System::Diagnostics::Debug::FlushInstructionCache;
 use windows::Win32::System::Memory::*;
-use windows::Win32::System::ProcessStatus::GetMappedFileNameW;
+use windows::Win32::System::ProcessStatus::{
+    GetMappedFileNameW, PSAPI_WORKING_SET_EX_INFORMATION, QueryWorkingSetEx,
+};
 use windows::Win32::System::SystemInformation::{GetSystemInfo, SYSTEM_INFO};
 use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcess, PROCESS_ALL_ACCESS};
 use windows::core::PCWSTR;
@@ -51,8 +55,12 @@ pub struct Mmap {
     area: Arc<SharedArea>,
     ptr: *mut u8,
     size: usize,
+    /// The number of bytes mapped in front of `ptr` that are hidden from the user, e.g. as the
+    /// file offset had to be aligned down to the allocation granularity.
+    offset: usize,
     flags: Flags,
-    protection: PAGE_PROTECTION_FLAGS,
+    protections: Protections<PAGE_PROTECTION_FLAGS>,
+    file: Option<Arc<File>>,
 }
 
 unsafe impl Send for Mmap {}
@@ -74,9 +82,33 @@ impl Mmap {
         self.size
     }
 
+    #[inline]
+    pub fn file(&self) -> Option<&File> {
+        self.file.as_deref()
+    }
+
+    /// Returns the start of the underlying memory mapping, including any hidden leading bytes.
+    #[inline]
+    fn base(&self) -> *mut u8 {
+        unsafe { self.ptr.sub(self.offset) }
+    }
+
+    /// Returns the start of the given byte range relative to the start of the underlying memory
+    /// mapping. A range that starts at the start of the memory mapping also covers any hidden
+    /// leading bytes, such that it starts at a page boundary.
+    #[inline]
+    fn real_start(&self, start: usize) -> usize {
+        if start == 0 { 0 } else { self.offset + start }
+    }
+
     pub fn lock(&mut self) -> Result<(), Error> {
-        let status =
-            unsafe { VirtualLock(self.ptr as *const std::ffi::c_void, self.size) }.as_bool();
+        let status = unsafe {
+            VirtualLock(
+                self.base() as *const std::ffi::c_void,
+                self.offset + self.size,
+            )
+        }
+        .as_bool();
 
         if !status {
             return Err(std::io::Error::last_os_error())?;
@@ -86,8 +118,13 @@ impl Mmap {
     }
 
     pub fn unlock(&mut self) -> Result<(), Error> {
-        let status =
-            unsafe { VirtualUnlock(self.ptr as *const std::ffi::c_void, self.size) }.as_bool();
+        let status = unsafe {
+            VirtualUnlock(
+                self.base() as *const std::ffi::c_void,
+                self.offset + self.size,
+            )
+        }
+        .as_bool();
 
         if !status {
             return Err(std::io::Error::last_os_error())?;
@@ -99,6 +136,10 @@ impl Mmap {
     pub fn flush(&self, range: Range<usize>) -> Result<(), Error> {
         self.flush_async(range)?;
 
+        if let Some(file) = &self.file {
+            file.sync_data()?;
+        }
+
         Ok(())
     }
 
@@ -122,13 +163,120 @@ impl Mmap {
         Ok(())
     }
 
+    pub fn residency(&self, range: Range<usize>) -> Result<Vec<bool>, Error> {
+        if range.start > range.end || range.end > self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        if range.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let page_size = MmapOptions::page_size();
+        let start = self.offset + range.start;
+        let start = start - start % page_size;
+        let size = self.offset + range.end - start;
+
+        let mut pages: Vec<PSAPI_WORKING_SET_EX_INFORMATION> = (0..size.div_ceil(page_size))
+            .map(|page| PSAPI_WORKING_SET_EX_INFORMATION {
+                VirtualAddress: unsafe { self.base().add(start + page * page_size) } as _,
+                ..Default::default()
+            })
+            .collect();
+
+        let status = unsafe {
+            QueryWorkingSetEx(
+                GetCurrentProcess(),
+                pages.as_mut_ptr() as _,
+                (pages.len() * std::mem::size_of::<PSAPI_WORKING_SET_EX_INFORMATION>()) as u32,
+            )
+        }
+        .as_bool();
+
+        if !status {
+            return Err(std::io::Error::last_os_error())?;
+        }
+
+        // The lowest bit of the attributes indicates whether the page is valid, i.e. resident.
+        Ok(pages
+            .iter()
+            .map(|page| unsafe { page.VirtualAttributes.Flags } & 1 != 0)
+            .collect())
+    }
+
     pub fn do_make(&mut self, protect: PAGE_PROTECTION_FLAGS) -> Result<(), Error> {
         let mut old_protect = PAGE_PROTECTION_FLAGS::default();
 
         let status = unsafe {
             VirtualProtect(
-                self.ptr as *mut std::ffi::c_void,
-                self.size,
+                self.base() as *mut std::ffi::c_void,
+                self.offset + self.size,
+                protect,
+                &mut old_protect,
+            )
+            .as_bool()
+        };
+
+        if !status {
+            return Err(std::io::Error::last_os_error())?;
+        }
+
+        self.protections.reset(protect);
+
+        Ok(())
+    }
+
+    pub fn protect_range(
+        &mut self,
+        range: Range<usize>,
+        protection: Protection,
+    ) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        let page_size = MmapOptions::page_size();
+        let start = self.real_start(range.start);
+        let end = self.offset + range.end;
+
+        if start % page_size != 0 || (end % page_size != 0 && range.end != self.size) {
+            return Err(Error::InvalidOffset);
+        }
+
+        if protection.contains(Protection::WRITE | Protection::EXECUTE)
+            && !self.flags.contains(Flags::JIT)
+        {
+            return Err(Error::UnsafeFlagNeeded(UnsafeMmapFlags::JIT));
+        }
+
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let copy_on_write = self.area.flags.contains(SharedFlags::FILE)
+            && self.flags.contains(Flags::COPY_ON_WRITE);
+
+        let protect = match (
+            protection.contains(Protection::READ),
+            protection.contains(Protection::WRITE),
+            protection.contains(Protection::EXECUTE),
+        ) {
+            (false, false, false) => PAGE_NOACCESS,
+            (true, false, false) => PAGE_READONLY,
+            (_, true, false) if copy_on_write => PAGE_WRITECOPY,
+            (_, true, false) => PAGE_READWRITE,
+            (false, false, true) => PAGE_EXECUTE,
+            (true, false, true) => PAGE_EXECUTE_READ,
+            (_, true, true) if copy_on_write => PAGE_EXECUTE_WRITECOPY,
+            (_, true, true) => PAGE_EXECUTE_READWRITE,
+        };
+
+        let mut old_protect = PAGE_PROTECTION_FLAGS::default();
+
+        let status = unsafe {
+            VirtualProtect(
+                self.base().add(start) as *mut std::ffi::c_void,
+                end - start,
                 protect,
                 &mut old_protect,
             )
@@ -139,11 +287,30 @@ impl Mmap {
             return Err(std::io::Error::last_os_error())?;
         }
 
-        self.protection = protect;
+        self.protections.set(range, protect);
 
         Ok(())
     }
 
+    pub fn protection_at(&self, offset: usize) -> Result<Protection, Error> {
+        if offset >= self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        let protection = match self.protections.get(offset) {
+            PAGE_EXECUTE => Protection::EXECUTE,
+            PAGE_EXECUTE_READ => Protection::READ | Protection::EXECUTE,
+            PAGE_EXECUTE_READWRITE | PAGE_EXECUTE_WRITECOPY => {
+                Protection::READ | Protection::WRITE | Protection::EXECUTE
+            }
+            PAGE_READONLY => Protection::READ,
+            PAGE_READWRITE | PAGE_WRITECOPY => Protection::READ | Protection::WRITE,
+            _ => Protection::empty(),
+        };
+
+        Ok(protection)
+    }
+
     pub fn flush_icache(&self) -> Result<(), Error> {
         // While the x86 and x86-64 architectures guarantee cache coherency between the L1
         // instruction and the L1 data cache, other architectures such as arm and aarch64 do not.
@@ -162,6 +329,48 @@ impl Mmap {
         Ok(())
     }
 
+    pub fn advise(&mut self, range: Range<usize>, advice: Advice) -> Result<(), Error> {
+        if range.start > range.end || range.end > self.size {
+            return Err(Error::InvalidOffset);
+        }
+
+        let start = self.real_start(range.start);
+
+        if start % MmapOptions::page_size() != 0 {
+            return Err(Error::InvalidOffset);
+        }
+
+        let ptr = unsafe { self.base().add(start) };
+        let size = self.offset + range.end - start;
+
+        match advice {
+            Advice::WillNeed => {
+                let entry = WIN32_MEMORY_RANGE_ENTRY {
+                    VirtualAddress: ptr as *mut std::ffi::c_void,
+                    NumberOfBytes: size,
+                };
+
+                let status =
+                    unsafe { PrefetchVirtualMemory(GetCurrentProcess(), &[entry], 0) }.as_bool();
+
+                if !status {
+                    return Err(std::io::Error::last_os_error())?;
+                }
+            }
+            Advice::Free => {
+                let status =
+                    unsafe { DiscardVirtualMemory(std::slice::from_raw_parts_mut(ptr, size)) };
+
+                if status != 0 {
+                    return Err(std::io::Error::from_raw_os_error(status as i32))?;
+                }
+            }
+            _ => return Err(Error::UnsupportedAdvice(advice)),
+        }
+
+        Ok(())
+    }
+
     pub fn make_none(&mut self) -> Result<(), Error> {
         self.do_make(PAGE_NOACCESS)
     }
@@ -204,10 +413,10 @@ impl Mmap {
         if !self.area.flags.contains(SharedFlags::FILE) {
             let ptr = unsafe {
                 VirtualAlloc(
-                    Some(self.ptr as *mut std::ffi::c_void),
-                    self.size,
+                    Some(self.base() as *mut std::ffi::c_void),
+                    self.offset + self.size,
                     MEM_COMMIT,
-                    self.protection,
+                    self.protections.base(),
                 )
             };
 
@@ -221,16 +430,33 @@ impl Mmap {
         Ok(())
     }
 
-    pub fn merge(&mut self, other: &Self) -> Result<(), Error> {
-        if self.area != other.area {
+    /// Microsoft Windows does not offer a way to resize an existing view or allocation, as there
+    /// is no equivalent to `mremap()`.
+    pub fn resize(&mut self, _new_size: usize, _policy: RemapPolicy) -> Result<(), Error> {
+        Err(Error::InvalidOperation)
+    }
+
+    pub fn merge(&mut self, other: &mut Self) -> Result<(), Error> {
+        if self.area != other.area || !same_file(&self.file, &other.file) {
             return Err(Error::BackingMismatch);
         }
 
+        // The hidden leading bytes of the other memory mapping would overlap with this one.
+        if other.offset != 0 {
+            return Err(Error::MustBeAdjacent);
+        }
+
         if self.flags != other.flags {
             return Err(Error::AttributeMismatch);
         }
 
+        if self.protections.base() != other.protections.base() {
+            return Err(Error::AttributeMismatch);
+        }
+
+        self.protections.append(self.size, &other.protections);
         self.size += other.size;
+        other.file = None;
 
         Ok(())
     }
@@ -240,7 +466,7 @@ impl Mmap {
             return Err(Error::InvalidOffset);
         }
 
-        if at % MmapOptions::page_size() != 0 {
+        if (self.offset + at) % MmapOptions::page_size() != 0 {
             return Err(Error::InvalidOffset);
         }
 
@@ -252,8 +478,10 @@ impl Mmap {
             area: self.area.clone(),
             ptr,
             size,
+            offset: 0,
             flags: self.flags,
-            protection: self.protection,
+            protections: self.protections.split_off(at),
+            file: self.file.clone(),
         })
     }
 
@@ -262,7 +490,7 @@ impl Mmap {
             return Err(Error::InvalidOffset);
         }
 
-        if at % MmapOptions::page_size() != 0 {
+        if (self.offset + at) % MmapOptions::page_size() != 0 {
             return Err(Error::InvalidOffset);
         }
 
@@ -275,8 +503,10 @@ impl Mmap {
             area: self.area.clone(),
             ptr,
             size,
+            offset: std::mem::take(&mut self.offset),
             flags: self.flags,
-            protection: self.protection,
+            protections: self.protections.split_to(at),
+            file: self.file.clone(),
         })
     }
 }
@@ -286,8 +516,8 @@ impl Drop for Mmap {
         if self.flags.contains(Flags::COMMITTED) {
             let _ = unsafe {
                 VirtualFree(
-                    self.ptr as *mut _,
-                    self.size,
+                    self.base() as *mut _,
+                    self.offset + self.size,
                     VIRTUAL_FREE_TYPE(MEM_DECOMMIT.0),
                 )
             };
@@ -298,22 +528,28 @@ impl Drop for Mmap {
 #[derive(Debug)]
 pub struct MmapOptions<'a> {
     address: Option<usize>,
-    file: Option<(&'a File, u64)>,
+    exact_address: bool,
+    file: Option<(MmapFile<'a>, u64)>,
     size: usize,
     flags: MmapFlags,
     unsafe_flags: UnsafeMmapFlags,
     page_size: Option<PageSize>,
+    guard_pages: (usize, usize),
+    alignment: Option<usize>,
 }
 
 impl<'a> MmapOptions<'a> {
     pub fn new(size: usize) -> Result<Self, Error> {
         Ok(Self {
             address: None,
+            exact_address: false,
             file: None,
             size,
             flags: MmapFlags::empty(),
             unsafe_flags: UnsafeMmapFlags::empty(),
             page_size: None,
+            guard_pages: (0, 0),
+            alignment: None,
         })
     }
 
@@ -350,8 +586,19 @@ impl<'a> MmapOptions<'a> {
         self
     }
 
+    pub fn with_exact_address(mut self, address: usize) -> Self {
+        self.address = Some(address);
+        self.exact_address = true;
+        self
+    }
+
     pub fn with_file(mut self, file: &'a File, offset: u64) -> Self {
-        self.file = Some((file, offset));
+        self.file = Some((MmapFile::Borrowed(file), offset));
+        self
+    }
+
+    pub fn with_owned_file(mut self, file: Arc<File>, offset: u64) -> Self {
+        self.file = Some((MmapFile::Owned(file), offset));
         self
     }
 
@@ -370,6 +617,34 @@ impl<'a> MmapOptions<'a> {
         self
     }
 
+    pub fn with_guard_pages(mut self, before: usize, after: usize) -> Self {
+        self.guard_pages = (before, after);
+        self
+    }
+
+    pub fn with_alignment(mut self, alignment: usize) -> Self {
+        self.alignment = Some(alignment);
+        self
+    }
+
+    /// Finds the start of an address range of the given size, such that the address is aligned to
+    /// the given alignment after skipping the given number of bytes. This reserves a large enough
+    /// address range to find such an address within, and then releases it again.
+    fn find_aligned_address(size: usize, alignment: usize, skip: usize) -> Result<usize, Error> {
+        let padded_size = size.checked_add(alignment).ok_or(Error::InvalidSize)?;
+        let ptr = unsafe { VirtualAlloc(None, padded_size, MEM_RESERVE, PAGE_NOACCESS) };
+
+        if ptr.is_null() {
+            return Err(std::io::Error::last_os_error())?;
+        }
+
+        let address = (ptr as usize + skip).next_multiple_of(alignment);
+
+        let _ = unsafe { VirtualFree(ptr, 0, VIRTUAL_FREE_TYPE(MEM_RELEASE.0)) };
+
+        Ok(address)
+    }
+
     /// This is a helper function that simply calls [`CreateFileMappingW`] and then [`CloseHandle`]
     /// to check if a file mapping can be created with the given protection. This is mostly needed
     /// to figure out whether a file mapping can be created with read, write and execute access.
@@ -384,7 +659,7 @@ impl<'a> MmapOptions<'a> {
         // Try creating a file mapping with the given protection.
         let file_mapping = unsafe {
             CreateFileMappingW(
-                HANDLE(file.as_raw_handle() as isize),
+                HANDLE(file.as_file().as_raw_handle() as isize),
                 None,
                 protection,
                 0,
@@ -412,6 +687,16 @@ impl<'a> MmapOptions<'a> {
     /// This is a helper function that goes through the process of setting up the desired memory
     /// mapping given the protection flag.
     fn do_map(self, protection: PAGE_PROTECTION_FLAGS, mut flags: Flags) -> Result<Mmap, Error> {
+        if let Some(alignment) = self.alignment {
+            if !alignment.is_power_of_two() {
+                return Err(Error::InvalidAlignment);
+            }
+
+            if self.address.is_some_and(|address| address % alignment != 0) {
+                return Err(Error::InvalidAlignment);
+            }
+        }
+
         // We have to check whether we can create the file mapping with write and execute
         // permissions. As Microsoft Windows won't let us set any access flags other than those
         // that have been set initially, we have to figure out the full set of access flags that
@@ -449,23 +734,77 @@ impl<'a> MmapOptions<'a> {
             map_access = FILE_MAP_COPY;
         }
 
+        // Guard pages are reserved together with the memory mapping, which is not possible for
+        // file mappings and large pages as these cannot be placed within an existing reservation.
+        let page_size = Self::page_size();
+        let guard_before = self.guard_pages.0.saturating_mul(page_size);
+        let guard_after = self.guard_pages.1.saturating_mul(page_size);
+        let guarded = guard_before != 0 || guard_after != 0;
+
+        if guarded && (self.file.is_some() || self.flags.contains(MmapFlags::HUGE_PAGES)) {
+            return Err(Error::InvalidOperation);
+        }
+
+        // Microsoft Windows rounds the address down to the allocation granularity, which would
+        // place the memory mapping elsewhere.
+        if self.exact_address {
+            let address = self
+                .address
+                .and_then(|address| address.checked_sub(guard_before))
+                .ok_or(Error::InvalidOffset)?;
+
+            if address % Self::allocation_granularity() != 0 {
+                return Err(Error::InvalidAlignment);
+            }
+        }
+
+        // The file offset must be aligned to the allocation granularity. Align it down and hide
+        // the leading bytes.
+        let skip = match &self.file {
+            Some((_, offset)) => (offset % Self::allocation_granularity() as u64) as usize,
+            _ => 0,
+        };
+
         let size = self.size;
-        let ptr = if let Some((file, offset)) = self.file {
+
+        // Allocations are only guaranteed to be aligned to the allocation granularity. Otherwise,
+        // look for an aligned address first.
+        let address = match (self.address, self.alignment) {
+            (None, Some(alignment)) if alignment > Self::allocation_granularity() => {
+                let reserve_size = size
+                    .checked_add(skip)
+                    .map(|size| size.next_multiple_of(page_size))
+                    .and_then(|size| size.checked_add(guard_before))
+                    .and_then(|size| size.checked_add(guard_after))
+                    .ok_or(Error::InvalidSize)?;
+
+                Some(Self::find_aligned_address(
+                    reserve_size,
+                    alignment,
+                    guard_before,
+                )?)
+            }
+            (address, _) => address,
+        };
+
+        let ptr = if let Some((file, offset)) = &self.file {
             if self.flags.contains(MmapFlags::HUGE_PAGES) {
                 map_access |= FILE_MAP_LARGE_PAGES;
                 map_protection |= SEC_LARGE_PAGES;
             }
 
+            // The file mapping has to cover the file up to the end of the view.
+            let max_size = *offset + size as u64;
+            let offset = *offset - skip as u64;
+            let size = size.checked_add(skip).ok_or(Error::InvalidSize)?;
+
             let file_mapping = unsafe {
                 CreateFileMappingW(
-                    HANDLE(file.as_raw_handle() as isize),
+                    HANDLE(file.as_file().as_raw_handle() as isize),
                     None,
                     map_protection,
-                    (match size.overflowing_shr(32) {
-                        (_, true) => 0,
-                        (size, false) => size,
-                    } & 0xffff_ffff) as u32,
-                    (size & 0xffff_ffff) as u32,
+                    ((max_size >> 32) & 0xffff_ffff) as u32,
+                    (max_size & 0xffff_ffff) as u32,
                     PCWSTR::null(),
                 )
             }?;
@@ -477,9 +816,16 @@ impl<'a> MmapOptions<'a> {
                     ((offset >> 32) & 0xffff_ffff) as u32,
                     (offset & 0xffff_ffff) as u32,
                     size,
-                    None,
+                    address.map(|address| address as *const std::ffi::c_void),
                 )
-            }?
+            }
+            .map_err(|e| {
+                if self.exact_address && e.code() == ERROR_INVALID_ADDRESS.to_hresult() {
+                    Error::AddressInUse
+                } else {
+                    e.into()
+                }
+            })?
             .0 as *mut u8;
 
             unsafe { CloseHandle(file_mapping) };
@@ -494,6 +840,23 @@ impl<'a> MmapOptions<'a> {
             }
 
             ptr
+        } else if guarded {
+            let address = match address {
+                Some(address) => Some(
+                    address
+                        .checked_sub(guard_before)
+                        .ok_or(Error::InvalidOffset)?
+                        as *const std::ffi::c_void,
+                ),
+                _ => None,
+            };
+
+            let size = guard_before
+                .checked_add(size.next_multiple_of(page_size))
+                .and_then(|size| size.checked_add(guard_after))
+                .ok_or(Error::InvalidSize)?;
+
+            (unsafe { VirtualAlloc(address, size, MEM_RESERVE, PAGE_NOACCESS) }) as *mut u8
         } else {
             let mut flags = if flags.contains(Flags::COMMITTED) {
                 MEM_COMMIT | MEM_RESERVE
@@ -507,8 +870,7 @@ impl<'a> MmapOptions<'a> {
 
             (unsafe {
                 VirtualAlloc(
-                    self.address
-                        .map(|address| address as *const std::ffi::c_void),
+                    address.map(|address| address as *const std::ffi::c_void),
                     size,
                     flags,
                     protection,
@@ -517,10 +879,39 @@ impl<'a> MmapOptions<'a> {
         };
 
         if ptr.is_null() {
-            return Err(std::io::Error::last_os_error())?;
+            let e = std::io::Error::last_os_error();
+
+            if self.exact_address && e.raw_os_error() == Some(ERROR_INVALID_ADDRESS.0 as i32) {
+                return Err(Error::AddressInUse);
+            }
+
+            return Err(e)?;
         }
 
         let size = self.size;
+        let area_ptr = ptr;
+        let ptr = unsafe { ptr.add(guard_before + skip) };
+
+        // Commit the memory mapping in between the guard pages, which remain reserved.
+        if guarded && flags.contains(Flags::COMMITTED) {
+            let status = unsafe {
+                VirtualAlloc(
+                    Some(ptr as *const std::ffi::c_void),
+                    size,
+                    MEM_COMMIT,
+                    protection,
+                )
+            };
+
+            if status.is_null() {
+                let e = std::io::Error::last_os_error();
+
+                let _ =
+                    unsafe { VirtualFree(area_ptr as *mut _, 0, VIRTUAL_FREE_TYPE(MEM_RELEASE.0)) };
+
+                return Err(e)?;
+            }
+        }
 
         if !self.flags.contains(MmapFlags::SHARED) {
             flags |= Flags::COPY_ON_WRITE;
@@ -537,7 +928,7 @@ impl<'a> MmapOptions<'a> {
         }
 
         let area = Arc::new(SharedArea {
-            ptr,
+            ptr: area_ptr,
             flags: shared_flags,
         });
 
@@ -545,8 +936,10 @@ impl<'a> MmapOptions<'a> {
             area,
             ptr,
             size,
+            offset: skip,
             flags,
-            protection,
+            protections: Protections::new(protection),
+            file: self.file.as_ref().and_then(|(file, _)| file.owned()),
         })
     }
 
@@ -626,6 +1019,40 @@ impl<'a> MmapOptions<'a> {
 use std::io::{BufRead, BufReader};
 use std::marker::PhantomData;
 
+/// Returns the address ranges within the given address range that are free in the virtual address
+/// space of the current process, i.e. that are neither committed nor reserved.
+pub fn free_ranges(range: Range<usize>) -> Result<Vec<Range<usize>>, Error> {
+    let mut free = vec![];
+    let mut address = range.start;
+
+    while address < range.end {
+        let mut info = MEMORY_BASIC_INFORMATION::default();
+
+        let size = unsafe {
+            VirtualQuery(
+                Some(address as _),
+                &mut info,
+                std::mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+
+        if size < std::mem::size_of::<MEMORY_BASIC_INFORMATION>() {
+            break;
+        }
+
+        let start = info.BaseAddress as usize;
+        let end = start + info.RegionSize;
+
+        if info.State == MEM_FREE {
+            free.push(start.max(range.start)..end.min(range.end));
+        }
+
+        address = end;
+    }
+
+    Ok(free)
+}
+
 pub struct MemoryAreas<B> {
     handle: HANDLE,
     address: usize,
@@ -730,7 +1157,13 @@ impl<B: BufRead> Iterator for MemoryAreas<B> {
                 range,
                 protection,
                 share_mode,
+                kind: match path {
+                    Some(_) => MemoryAreaKind::File,
+                    _ => MemoryAreaKind::Anonymous,
+                },
                 path,
+                device: (0, 0),
+                inode: 0,
             }));
         }
     }
@@ -0,0 +1,335 @@
+//! This module tracks writes to memory mappings by write-protecting their pages and handling the
+//! resulting `SIGSEGV`, which is used as a fallback when the kernel does not track soft-dirty
+//! pages.
+
+use crate::error::Error;
+use nix::errno::Errno;
+use nix::sys::mman::ProtFlags;
+use std::ffi::c_void;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// The `si_code` of a `SIGSEGV` that has been caused by an access the protection of the page does
+/// not allow, as opposed to an access to an unmapped page or a protection key violation.
+const SEGV_ACCERR: libc::c_int = 2;
+
+/// The maximum number of memory mappings of which the writes can be tracked at the same time.
+const MAX_TRACKED: usize = 64;
+
+/// The pages of a memory mapping of which the writes are being tracked, as shared with the signal
+/// handler.
+#[derive(Debug)]
+struct Tracked {
+    start: usize,
+    page_size: usize,
+    /// The protection of every page to restore upon the first write.
+    protections: Box<[ProtFlags]>,
+    /// The pages of which the protection is being restored.
+    pending: Box<[AtomicU64]>,
+    /// The pages that have been written to.
+    dirty: Box<[AtomicU64]>,
+}
+
+impl Tracked {
+    fn is_set(bits: &[AtomicU64], page: usize) -> bool {
+        bits[page / 64].load(Ordering::Acquire) & (1 << (page % 64)) != 0
+    }
+
+    fn set(bits: &[AtomicU64], page: usize) -> bool {
+        bits[page / 64].fetch_or(1 << (page % 64), Ordering::AcqRel) & (1 << (page % 64)) != 0
+    }
+
+    /// Records the write to the page containing the given address and restores its protection,
+    /// such that the write can be retried. Returns `false` if the fault has not been caused by
+    /// tracking the writes, in which case it has to be forwarded.
+    fn record_write(&self, address: usize) -> bool {
+        // The slot may have been reused for other pages since the address range was checked.
+        let page = match address.checked_sub(self.start) {
+            Some(offset) => offset / self.page_size,
+            _ => return false,
+        };
+
+        if page >= self.protections.len() {
+            return false;
+        }
+
+        // Pages that are not writable have not been write-protected, so the fault is genuine.
+        if !self.protections[page].contains(ProtFlags::PROT_WRITE) {
+            return false;
+        }
+
+        // Another thread is restoring the protection of the page. Retry the write once it is done.
+        if Self::set(&self.pending, page) {
+            return !Self::is_set(&self.dirty, page);
+        }
+
+        let status = unsafe {
+            libc::mprotect(
+                (self.start + page * self.page_size) as *mut c_void,
+                self.page_size,
+                self.protections[page].bits(),
+            )
+        };
+
+        Self::set(&self.dirty, page);
+
+        status == 0
+    }
+
+    /// Forgets about the pages that have been written to and write-protects them again.
+    fn write_protect(&self) -> Result<(), Error> {
+        for (pending, dirty) in self.pending.iter().zip(self.dirty.iter()) {
+            pending.store(0, Ordering::Release);
+            dirty.store(0, Ordering::Release);
+        }
+
+        self.protect(|protect| protect - ProtFlags::PROT_WRITE)
+    }
+
+    /// Applies the given function to the protection of every run of pages that share the same
+    /// protection.
+    fn protect(&self, f: impl Fn(ProtFlags) -> ProtFlags) -> Result<(), Error> {
+        let mut page = 0;
+
+        while page < self.protections.len() {
+            let protect = self.protections[page];
+            let mut end = page + 1;
+
+            while end < self.protections.len() && self.protections[end] == protect {
+                end += 1;
+            }
+
+            let status = unsafe {
+                libc::mprotect(
+                    (self.start + page * self.page_size) as *mut c_void,
+                    (end - page) * self.page_size,
+                    f(protect).bits(),
+                )
+            };
+
+            Errno::result(status)?;
+
+            page = end;
+        }
+
+        Ok(())
+    }
+}
+
+/// A slot in which the signal handler looks up the tracked pages. The address range is stored
+/// separately, such that the signal handler only dereferences the tracked pages of the memory
+/// mapping that the fault occurred in, which cannot be dropped while it is being accessed.
+struct Slot {
+    start: AtomicUsize,
+    end: AtomicUsize,
+    tracked: AtomicPtr<Tracked>,
+}
+
+static SLOTS: [Slot; MAX_TRACKED] = [const {
+    Slot {
+        start: AtomicUsize::new(0),
+        end: AtomicUsize::new(0),
+        tracked: AtomicPtr::new(std::ptr::null_mut()),
+    }
+}; MAX_TRACKED];
+
+/// Serializes claiming and releasing the slots.
+static SLOTS_LOCK: Mutex<()> = Mutex::new(());
+
+/// The `SIGSEGV` handler that was installed before, to which any faults that have not been caused
+/// by tracking writes are forwarded.
+static PREVIOUS: OnceLock<libc::sigaction> = OnceLock::new();
+
+fn install_handler() -> Result<(), Error> {
+    static INSTALLED: OnceLock<Result<(), Errno>> = OnceLock::new();
+
+    let result = INSTALLED.get_or_init(|| {
+        let mut previous: libc::sigaction = unsafe { std::mem::zeroed() };
+        Errno::result(unsafe { libc::sigaction(libc::SIGSEGV, std::ptr::null(), &mut previous) })?;
+        let _ = PREVIOUS.set(previous);
+
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = handle_sigsegv as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        unsafe { libc::sigemptyset(&mut action.sa_mask) };
+
+        Errno::result(unsafe { libc::sigaction(libc::SIGSEGV, &action, std::ptr::null_mut()) })?;
+
+        Ok(())
+    });
+
+    Ok((*result)?)
+}
+
+extern "C" fn handle_sigsegv(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut c_void,
+) {
+    let errno = Errno::last_raw();
+
+    if unsafe { (*info).si_code } == SEGV_ACCERR {
+        let address = unsafe { (*info).si_addr() } as usize;
+
+        for slot in &SLOTS {
+            if address < slot.start.load(Ordering::Acquire)
+                || address >= slot.end.load(Ordering::Acquire)
+            {
+                continue;
+            }
+
+            let Some(tracked) = (unsafe { slot.tracked.load(Ordering::Acquire).as_ref() }) else {
+                continue;
+            };
+
+            if tracked.record_write(address) {
+                Errno::set_raw(errno);
+                return;
+            }
+        }
+    }
+
+    unsafe { forward(signal, info, context) };
+}
+
+/// Forwards the signal to the previously installed handler. If there is none, then the default
+/// action is restored, such that the process terminates once the faulting access is retried.
+unsafe fn forward(signal: libc::c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    match PREVIOUS.get() {
+        Some(previous)
+            if previous.sa_sigaction != libc::SIG_DFL && previous.sa_sigaction != libc::SIG_IGN =>
+        {
+            if previous.sa_flags & libc::SA_SIGINFO != 0 {
+                let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut c_void) =
+                    unsafe { std::mem::transmute(previous.sa_sigaction) };
+
+                handler(signal, info, context);
+            } else {
+                let handler: extern "C" fn(libc::c_int) =
+                    unsafe { std::mem::transmute(previous.sa_sigaction) };
+
+                handler(signal);
+            }
+        }
+        _ => {
+            let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+            action.sa_sigaction = libc::SIG_DFL;
+
+            unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) };
+        }
+    }
+}
+
+/// The number of memory mappings of which the writes are being tracked, such that unmapping
+/// memory mappings does not have to look through the slots if there are none.
+static TRACKED: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts tracking the writes to the pages starting at the given page aligned address by
+/// write-protecting them, where the protections are the current protections of the pages. If the
+/// writes to the pages are already being tracked, this forgets about the pages that have been
+/// written to and write-protects them again.
+///
+/// # Safety
+///
+/// The pages must remain mapped with the given protections until [`untrack()`] is called.
+pub unsafe fn track(ptr: NonNull<c_void>, protections: Vec<ProtFlags>) -> Result<(), Error> {
+    install_handler()?;
+
+    let start = ptr.as_ptr() as usize;
+    let _lock = SLOTS_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+    if let Some(tracked) = find(start) {
+        return tracked.write_protect();
+    }
+
+    // There is no room to track any more memory mappings.
+    let slot = SLOTS
+        .iter()
+        .position(|slot| slot.tracked.load(Ordering::Acquire).is_null())
+        .ok_or(Error::InvalidOperation)?;
+
+    let words = protections.len().div_ceil(64);
+    let tracked = Box::into_raw(Box::new(Tracked {
+        start,
+        page_size: crate::MmapOptions::page_size(),
+        protections: protections.into_boxed_slice(),
+        pending: (0..words).map(|_| AtomicU64::new(0)).collect(),
+        dirty: (0..words).map(|_| AtomicU64::new(0)).collect(),
+    }));
+    let end = {
+        let tracked = unsafe { &*tracked };
+        start + tracked.protections.len() * tracked.page_size
+    };
+
+    SLOTS[slot].tracked.store(tracked, Ordering::Release);
+    SLOTS[slot].end.store(end, Ordering::Release);
+    SLOTS[slot].start.store(start, Ordering::Release);
+    TRACKED.fetch_add(1, Ordering::AcqRel);
+
+    if let Err(e) = unsafe { &*tracked }.write_protect() {
+        release(slot);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Returns `true` if the page with the given index has been written to since the writes to the
+/// pages starting at the given address are being tracked, or if they are not being tracked.
+pub fn is_written(ptr: NonNull<c_void>, page: usize) -> bool {
+    if TRACKED.load(Ordering::Acquire) == 0 {
+        return true;
+    }
+
+    let _lock = SLOTS_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+    find(ptr.as_ptr() as usize).is_none_or(|tracked| {
+        page >= tracked.protections.len() || Tracked::is_set(&tracked.dirty, page)
+    })
+}
+
+/// Stops tracking the writes to the pages starting at the given address, which restores their
+/// protection. This does nothing if the writes to the pages are not being tracked.
+pub fn untrack(ptr: NonNull<c_void>) {
+    if TRACKED.load(Ordering::Acquire) == 0 {
+        return;
+    }
+
+    let _lock = SLOTS_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    let start = ptr.as_ptr() as usize;
+
+    if let Some(slot) = SLOTS
+        .iter()
+        .position(|slot| slot.start.load(Ordering::Acquire) == start)
+    {
+        if let Some(tracked) = find(start) {
+            let _ = tracked.protect(|protect| protect);
+        }
+
+        release(slot);
+    }
+}
+
+/// Looks up the tracked pages starting at the given address. The slots must be locked.
+fn find(start: usize) -> Option<&'static Tracked> {
+    SLOTS
+        .iter()
+        .find(|slot| slot.start.load(Ordering::Acquire) == start)
+        .and_then(|slot| unsafe { slot.tracked.load(Ordering::Acquire).as_ref() })
+}
+
+/// Releases the slot and frees the tracked pages. The slots must be locked.
+fn release(slot: usize) {
+    let slot = &SLOTS[slot];
+
+    slot.start.store(0, Ordering::Release);
+    slot.end.store(0, Ordering::Release);
+
+    let tracked = slot.tracked.swap(std::ptr::null_mut(), Ordering::AcqRel);
+    TRACKED.fetch_sub(1, Ordering::AcqRel);
+
+    // The signal handler only accesses the tracked pages upon faults within them, which cannot
+    // occur any more as their owner is being modified or dropped.
+    drop(unsafe { Box::from_raw(tracked) });
+}
@@ -0,0 +1,197 @@
+//! This module implements a reader for `/proc/<pid>/pagemap` on Linux.
+
+use crate::MmapOptions;
+use crate::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::sync::OnceLock;
+
+const PRESENT: u64 = 1 << 63;
+const SWAPPED: u64 = 1 << 62;
+const FILE_OR_SHARED: u64 = 1 << 61;
+const EXCLUSIVE: u64 = 1 << 56;
+const SOFT_DIRTY: u64 = 1 << 55;
+const PFN_MASK: u64 = (1 << 55) - 1;
+
+/// Describes the state of a single page in the virtual address space of a process, as reported by
+/// `/proc/<pid>/pagemap`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PageInfo {
+    /// The virtual address of the page.
+    pub(crate) address: usize,
+    /// The raw 64-bit pagemap entry.
+    pub(crate) entry: u64,
+}
+
+impl PageInfo {
+    /// The virtual address of the page.
+    #[inline]
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Whether the page is present in RAM.
+    #[inline]
+    pub fn present(&self) -> bool {
+        self.entry & PRESENT != 0
+    }
+
+    /// Whether the page has been swapped out.
+    #[inline]
+    pub fn swapped(&self) -> bool {
+        self.entry & SWAPPED != 0
+    }
+
+    /// Whether the page is backed by a file or is shared anonymous memory.
+    #[inline]
+    pub fn file_or_shared(&self) -> bool {
+        self.entry & FILE_OR_SHARED != 0
+    }
+
+    /// Whether the page is mapped exclusively by this process. Since Linux 4.2.
+    #[inline]
+    pub fn exclusive(&self) -> bool {
+        self.entry & EXCLUSIVE != 0
+    }
+
+    /// Whether the page has been written to since the soft-dirty bits were last cleared. See
+    /// `Documentation/admin-guide/mm/soft-dirty.rst` in the Linux kernel for more information.
+    #[inline]
+    pub fn soft_dirty(&self) -> bool {
+        self.entry & SOFT_DIRTY != 0
+    }
+
+    /// The page frame number (PFN) of the physical page, if the page is present. Since Linux 4.2,
+    /// the PFN is only reported to processes with the `CAP_SYS_ADMIN` capability, such that this
+    /// returns `None` for unprivileged processes.
+    #[inline]
+    pub fn pfn(&self) -> Option<u64> {
+        if !self.present() {
+            return None;
+        }
+
+        match self.entry & PFN_MASK {
+            0 => None,
+            pfn => Some(pfn),
+        }
+    }
+
+    /// The swap type and the offset within the swap area, if the page has been swapped out.
+    #[inline]
+    pub fn swap_entry(&self) -> Option<(u8, u64)> {
+        if !self.swapped() {
+            return None;
+        }
+
+        Some(((self.entry & 0x1f) as u8, (self.entry & PFN_MASK) >> 5))
+    }
+}
+
+/// A reader for the page table of a process, which provides information on the physical pages
+/// backing its virtual address space.
+#[derive(Debug)]
+pub struct PageMap {
+    file: File,
+}
+
+impl PageMap {
+    /// Opens the page map of the specified process. If no process ID is given, then it opens the
+    /// page map of the current process.
+    pub fn open(pid: Option<u32>) -> Result<Self, Error> {
+        let path = match pid {
+            Some(pid) => format!("/proc/{}/pagemap", pid),
+            _ => "/proc/self/pagemap".to_string(),
+        };
+
+        let file = File::open(path)?;
+
+        Ok(Self { file })
+    }
+
+    /// Clears the soft-dirty bits of all the pages of the specified process by writing to
+    /// `/proc/<pid>/clear_refs`. If no process ID is given, then it clears the soft-dirty bits of
+    /// the current process. Afterwards, [`PageInfo::soft_dirty()`] only reports the pages that
+    /// have been written to since.
+    ///
+    /// This returns [`Error::SoftDirtyUnsupported`] if the kernel does not track soft-dirty pages,
+    /// see [`PageMap::soft_dirty_supported()`].
+    pub fn clear_soft_dirty(pid: Option<u32>) -> Result<(), Error> {
+        if !Self::soft_dirty_supported()? {
+            return Err(Error::SoftDirtyUnsupported);
+        }
+
+        let path = match pid {
+            Some(pid) => format!("/proc/{}/clear_refs", pid),
+            _ => "/proc/self/clear_refs".to_string(),
+        };
+
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        file.write_all(b"4")?;
+
+        Ok(())
+    }
+
+    /// Returns `true` if the kernel tracks soft-dirty pages, i.e. if it has been built with
+    /// `CONFIG_MEM_SOFT_DIRTY`. Otherwise, [`PageInfo::soft_dirty()`] is never set.
+    ///
+    /// This is determined once by writing to a newly mapped page and checking whether it is
+    /// reported as soft-dirty.
+    pub fn soft_dirty_supported() -> Result<bool, Error> {
+        static SUPPORTED: OnceLock<bool> = OnceLock::new();
+
+        if let Some(supported) = SUPPORTED.get() {
+            return Ok(*supported);
+        }
+
+        let mut mapping = MmapOptions::new(MmapOptions::page_size())?.map_mut()?;
+        mapping[0] = 1;
+
+        let page = Self::open(None)?.query(mapping.start())?;
+
+        Ok(*SUPPORTED.get_or_init(|| page.soft_dirty()))
+    }
+
+    /// Retrieves information about the page containing the given virtual address.
+    pub fn query(&self, address: usize) -> Result<PageInfo, Error> {
+        let mut pages = self.query_range(address..address + 1)?;
+
+        pages.pop().ok_or(Error::InvalidOffset)
+    }
+
+    /// Retrieves information about the pages covering the given virtual address range.
+    pub fn query_range(&self, range: Range<usize>) -> Result<Vec<PageInfo>, Error> {
+        if range.start > range.end {
+            return Err(Error::InvalidOffset);
+        }
+
+        let page_size = MmapOptions::page_size();
+        let start = range.start / page_size;
+        let end = range.end.div_ceil(page_size);
+
+        let mut bytes = vec![0u8; (end - start) * 8];
+        self.file.read_exact_at(&mut bytes, start as u64 * 8)?;
+
+        Ok(bytes
+            .chunks_exact(8)
+            .enumerate()
+            .map(|(index, entry)| PageInfo {
+                address: (start + index) * page_size,
+                entry: u64::from_ne_bytes(entry.try_into().unwrap()),
+            })
+            .collect())
+    }
+
+    /// Retrieves information about the pages of the given memory mapping, e.g. an [`Mmap`] or
+    /// [`MmapMut`].
+    ///
+    /// [`Mmap`]: crate::Mmap
+    /// [`MmapMut`]: crate::MmapMut
+    pub fn query_mapping<M: AsRef<[u8]>>(&self, mapping: &M) -> Result<Vec<PageInfo>, Error> {
+        let bytes = mapping.as_ref();
+        let start = bytes.as_ptr() as usize;
+
+        self.query_range(start..start + bytes.len())
+    }
+}
@@ -0,0 +1,163 @@
+//! This module implements reading and writing the memory of other processes on Linux.
+
+use crate::areas::MemoryArea;
+use crate::error::Error;
+use nix::errno::Errno;
+use nix::sys::uio::{RemoteIoVec, process_vm_readv};
+use nix::unistd::Pid;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, IoSliceMut};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::fs::FileExt;
+
+/// A handle to the memory of a process, e.g. one of the processes that can be enumerated using
+/// [`MemoryAreas::open()`], to read and write its contents.
+///
+/// This opens `/proc/<pid>/mem` together with a pidfd for the process once, which requires the
+/// permission to trace the process, see `ptrace(2)`. Both refer to the process they have been
+/// opened for, such that this never accesses another process that has been assigned the same
+/// process ID after the process exited.
+///
+/// Reading uses `process_vm_readv()`, which is then verified to have read from the original
+/// process using the pidfd. If it is not available, e.g. before Linux 5.3 which introduced
+/// pidfds, or if the pages are not accessible to it, this falls back to `/proc/<pid>/mem`.
+///
+/// Writing always uses `/proc/<pid>/mem` rather than `process_vm_writev()`, as a write through
+/// the process ID cannot be undone once it turns out to have reached another process. This also
+/// allows writing to read-only pages, e.g. to set a breakpoint.
+///
+/// [`MemoryAreas::open()`]: crate::MemoryAreas::open
+#[derive(Debug)]
+pub struct ProcessMemory {
+    pid: u32,
+    file: File,
+    /// The pidfd of the process, or `None` if pidfds are not supported.
+    pidfd: Option<OwnedFd>,
+}
+
+impl ProcessMemory {
+    /// Opens the memory of the specified process. If no process ID is given, then it opens the
+    /// memory of the current process. This fails if the process does not exist.
+    ///
+    /// The memory is opened for reading and writing, or only for reading if writing is not
+    /// permitted, in which case [`ProcessMemory::write()`] fails.
+    pub fn open(pid: Option<u32>) -> Result<Self, Error> {
+        let pid = pid.unwrap_or_else(std::process::id);
+
+        let status = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+
+        let pidfd = match Errno::result(status) {
+            Ok(fd) => Some(unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) }),
+            Err(Errno::ENOSYS) => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let path = format!("/proc/{pid}/mem");
+
+        let file = match OpenOptions::new().read(true).write(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                OpenOptions::new().read(true).open(&path)?
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let memory = Self { pid, file, pidfd };
+
+        // The process ID may have been reused before the memory was opened.
+        if memory.has_exited() {
+            return Err(Errno::ESRCH.into());
+        }
+
+        Ok(memory)
+    }
+
+    /// Returns the process ID of the process.
+    #[inline]
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Returns `true` if the process has exited according to its pidfd, after which its process
+    /// ID may be reused.
+    fn has_exited(&self) -> bool {
+        let Some(pidfd) = &self.pidfd else {
+            return false;
+        };
+
+        let mut fds = [libc::pollfd {
+            fd: pidfd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), 1, 0) };
+
+        ready > 0
+    }
+
+    /// Reads the memory of the process starting at the given address into the buffer. This fails
+    /// if the buffer cannot be filled entirely, e.g. because part of the address range is not
+    /// mapped, or if the process has exited.
+    pub fn read(&self, address: usize, buf: &mut [u8]) -> Result<(), Error> {
+        if buf.is_empty() {
+            return Ok(());
+        }
+
+        let mut count = 0;
+
+        if self.pidfd.is_some() {
+            let remote = [RemoteIoVec {
+                base: address,
+                len: buf.len(),
+            }];
+
+            let pid = Pid::from_raw(self.pid.try_into()?);
+
+            count = match process_vm_readv(pid, &mut [IoSliceMut::new(buf)], &remote) {
+                Ok(count) => count,
+                Err(Errno::ENOSYS | Errno::EFAULT | Errno::EPERM | Errno::ESRCH) => 0,
+                Err(e) => return Err(e.into()),
+            };
+
+            // The process is still alive, so the process ID has referred to it during the read.
+            // Otherwise, discard what has been read, as it may come from another process.
+            if self.has_exited() {
+                count = 0;
+            }
+        }
+
+        if count < buf.len() {
+            self.file
+                .read_exact_at(&mut buf[count..], (address + count) as u64)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the buffer to the memory of the process starting at the given address. This fails
+    /// if the buffer cannot be written entirely, or if the process has exited.
+    ///
+    /// # Safety
+    ///
+    /// When writing to the memory of the current process, the caller must ensure that this does
+    /// not invalidate any references to that memory, and that the memory is not being accessed
+    /// concurrently.
+    pub unsafe fn write(&self, address: usize, buf: &[u8]) -> Result<(), Error> {
+        self.file.write_all_at(buf, address as u64)?;
+
+        Ok(())
+    }
+
+    /// Reads the contents of the given memory area of the process, e.g. as returned by
+    /// [`MemoryAreas::open()`].
+    ///
+    /// [`MemoryAreas::open()`]: crate::MemoryAreas::open
+    pub fn read_area(&self, area: &MemoryArea) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![0u8; area.end() - area.start()];
+
+        self.read(area.start(), &mut bytes)?;
+
+        Ok(bytes)
+    }
+}
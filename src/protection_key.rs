@@ -0,0 +1,176 @@
+//! This module implements memory protection keys on Linux.
+
+use crate::error::Error;
+use bitflags::bitflags;
+use nix::errno::Errno;
+use nix::sys::mman::ProtFlags;
+use std::ffi::c_void;
+use std::ptr::NonNull;
+
+bitflags! {
+    /// The access rights of the current thread to the pages that have been assigned a protection
+    /// key. These restrict the access on top of the protection of the pages.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub struct ProtectionKeyRights: u32 {
+        /// Disables any data access to the pages.
+        const DISABLE_ACCESS = 1 << 0;
+
+        /// Disables writing to the pages.
+        const DISABLE_WRITE  = 1 << 1;
+    }
+}
+
+/// Assigns the protection key to the pages in the given address range, while changing their
+/// protection to the given protection.
+pub(crate) unsafe fn pkey_mprotect(
+    ptr: NonNull<c_void>,
+    size: usize,
+    protect: ProtFlags,
+    key: libc::c_int,
+) -> Result<(), Error> {
+    let status = unsafe {
+        libc::syscall(
+            libc::SYS_pkey_mprotect,
+            ptr.as_ptr(),
+            size,
+            protect.bits(),
+            key,
+        )
+    };
+
+    match Errno::result(status) {
+        Ok(_) => Ok(()),
+        Err(Errno::ENOSYS) => Err(Error::InvalidOperation),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A memory protection key, which allows a thread to restrict its own access to the pages that
+/// have been assigned the key by only updating a register, rather than changing the protection of
+/// the pages using `mprotect()`. The access rights are tracked per thread, such that other threads
+/// are not affected. The pages can be assigned the key using [`MmapOptions::with_protection_key()`]
+/// or [`MmapMut::assign_key()`].
+///
+/// Memory protection keys require hardware support, e.g. Intel MPK on x86-64. The key is freed
+/// when it is dropped, after which it may be allocated again, while any pages that have been
+/// assigned the key keep it. See [`ProtectionKey::set_rights()`] for the consequences.
+///
+/// [`MmapOptions::with_protection_key()`]: crate::MmapOptions::with_protection_key
+/// [`MmapMut::assign_key()`]: crate::MmapMut::assign_key
+#[derive(Debug)]
+pub struct ProtectionKey {
+    key: libc::c_int,
+}
+
+impl ProtectionKey {
+    /// Allocates a new protection key with the given initial access rights for the current
+    /// thread. Other threads get the default access rights of the process, which typically deny
+    /// any access. Returns [`Error::InvalidOperation`] if the CPU or the kernel does not support
+    /// memory protection keys, or if there are no protection keys left.
+    pub fn new(rights: ProtectionKeyRights) -> Result<Self, Error> {
+        let status = unsafe { libc::syscall(libc::SYS_pkey_alloc, 0, rights.bits()) };
+
+        let key = match Errno::result(status) {
+            Ok(key) => key as libc::c_int,
+            // Without support for memory protection keys, the kernel reports that there are no
+            // keys left, as only the default key exists.
+            Err(Errno::EINVAL | Errno::ENOSPC | Errno::ENOSYS) => {
+                return Err(Error::InvalidOperation);
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self { key })
+    }
+
+    /// Returns the number of the protection key.
+    #[inline]
+    pub fn key(&self) -> u32 {
+        self.key as u32
+    }
+
+    /// Returns the access rights of the current thread to the pages that have been assigned this
+    /// protection key. This is only supported on x86-64 and returns [`Error::InvalidOperation`]
+    /// otherwise.
+    pub fn rights(&self) -> Result<ProtectionKeyRights, Error> {
+        let pkru = read_pkru()?;
+
+        Ok(ProtectionKeyRights::from_bits_truncate(
+            pkru >> (2 * self.key),
+        ))
+    }
+
+    /// Changes the access rights of the current thread to the pages that have been assigned this
+    /// protection key. This is only supported on x86-64 and returns [`Error::InvalidOperation`]
+    /// otherwise.
+    ///
+    /// # Safety
+    ///
+    /// Accessing the pages in a way the access rights do not allow faults, so the caller must
+    /// ensure that the pages are not accessed through any references in the meantime, e.g. the
+    /// slices handed out by [`MmapMut`].
+    ///
+    /// As the key number is reused once a protection key has been dropped, this also applies to
+    /// any pages that are still assigned a protection key that has been dropped before this one
+    /// was allocated.
+    ///
+    /// [`MmapMut`]: crate::MmapMut
+    pub unsafe fn set_rights(&self, rights: ProtectionKeyRights) -> Result<(), Error> {
+        let shift = 2 * self.key;
+        let pkru = read_pkru()?;
+        let pkru = (pkru & !(0b11 << shift)) | (rights.bits() << shift);
+
+        write_pkru(pkru)
+    }
+}
+
+impl Drop for ProtectionKey {
+    fn drop(&mut self) {
+        let _ = unsafe { libc::syscall(libc::SYS_pkey_free, self.key) };
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn read_pkru() -> Result<u32, Error> {
+    let pkru: u32;
+
+    // A protection key has been allocated, so the CPU and the kernel support RDPKRU.
+    unsafe {
+        std::arch::asm!(
+            "rdpkru",
+            in("ecx") 0,
+            out("eax") pkru,
+            out("edx") _,
+            options(nomem, nostack, preserves_flags),
+        );
+    }
+
+    Ok(pkru)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn write_pkru(pkru: u32) -> Result<(), Error> {
+    // A protection key has been allocated, so the CPU and the kernel support WRPKRU. This changes
+    // which memory accesses fault, such that it must not be marked as `nomem`.
+    unsafe {
+        std::arch::asm!(
+            "wrpkru",
+            in("eax") pkru,
+            in("ecx") 0,
+            in("edx") 0,
+            options(nostack, preserves_flags),
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn read_pkru() -> Result<u32, Error> {
+    Err(Error::InvalidOperation)
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn write_pkru(_pkru: u32) -> Result<(), Error> {
+    Err(Error::InvalidOperation)
+}
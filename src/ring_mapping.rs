@@ -0,0 +1,114 @@
+//! This module implements ring buffer mappings, where the same pages are mapped twice back to back.
+
+use crate::error::Error;
+use crate::mmap::{MmapFlags, MmapMut, MmapOptions, UnsafeMmapFlags, anonymous_shared_memory};
+
+/// A memory mapping for ring buffers, also known as a "magic ring buffer", where the same pages
+/// are mapped twice directly after each other. Accessing the memory at offset `size + i` accesses
+/// the same byte as accessing the memory at offset `i`, such that any range of at most `size`
+/// bytes can be accessed as a single contiguous slice, even if it wraps around the end of the ring
+/// buffer.
+///
+/// The address range is first reserved as inaccessible memory, after which anonymous shared memory
+/// is mapped into both halves, i.e. `memfd_create()` on Linux and Android and an unlinked POSIX
+/// shared memory object on other platforms.
+#[derive(Debug)]
+pub struct RingMapping {
+    lower: MmapMut,
+    upper: MmapMut,
+}
+
+impl RingMapping {
+    /// Creates a new ring buffer mapping of the given size in bytes, such that `2 * size` bytes of
+    /// address space are used. The size must be a non-zero multiple of the page size, otherwise
+    /// [`Error::InvalidSize`] is returned.
+    pub fn new(size: usize) -> Result<Self, Error> {
+        if size == 0 || size % MmapOptions::page_size() != 0 {
+            return Err(Error::InvalidSize);
+        }
+
+        let file = anonymous_shared_memory("mmap-rs-ring", size)?;
+        let reservation =
+            MmapOptions::new(size.checked_mul(2).ok_or(Error::InvalidSize)?)?.reserve_none()?;
+        let start = reservation.start();
+
+        // Both halves are mapped over the reservation, which is then leaked as the address range
+        // is owned by the halves from then on.
+        let map_half = |address| unsafe {
+            MmapOptions::new(size)?
+                .with_address(address)
+                .with_file(&file, 0)
+                .with_flags(MmapFlags::SHARED)
+                .with_unsafe_flags(UnsafeMmapFlags::MAP_FIXED)
+                .map_mut()
+        };
+
+        let lower = map_half(start)?;
+        let upper = match map_half(start + size) {
+            Ok(upper) => upper,
+            Err(e) => {
+                // The reservation still covers the lower half and unmaps it.
+                std::mem::forget(lower);
+                return Err(e);
+            }
+        };
+
+        std::mem::forget(reservation);
+
+        Ok(Self { lower, upper })
+    }
+
+    /// Returns the size of the ring buffer in bytes, i.e. half the size of the address range.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.lower.size()
+    }
+
+    /// Returns a raw pointer to the start of the ring buffer.
+    #[inline]
+    pub fn as_ptr(&self) -> *const u8 {
+        self.lower.as_ptr()
+    }
+
+    /// Returns a raw mutable pointer to the start of the ring buffer.
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.lower.as_mut_ptr()
+    }
+
+    /// Extracts a slice of `len` bytes starting at offset `start`, which may wrap around the end of
+    /// the ring buffer. Returns [`Error::InvalidOffset`] if `start` is not within the ring buffer,
+    /// or [`Error::InvalidSize`] if `len` exceeds its size.
+    pub fn as_slice(&self, start: usize, len: usize) -> Result<&[u8], Error> {
+        self.check_range(start, len)?;
+
+        // The range is within the two halves of the address range and does not cover any byte
+        // twice.
+        Ok(unsafe { std::slice::from_raw_parts(self.as_ptr().add(start), len) })
+    }
+
+    /// Extracts a mutable slice of `len` bytes starting at offset `start`, which may wrap around
+    /// the end of the ring buffer. Returns [`Error::InvalidOffset`] if `start` is not within the
+    /// ring buffer, or [`Error::InvalidSize`] if `len` exceeds its size.
+    pub fn as_mut_slice(&mut self, start: usize, len: usize) -> Result<&mut [u8], Error> {
+        self.check_range(start, len)?;
+
+        // The range is within the two halves of the address range and does not cover any byte
+        // twice, such that the slice does not alias itself.
+        Ok(unsafe { std::slice::from_raw_parts_mut(self.as_mut_ptr().add(start), len) })
+    }
+
+    fn check_range(&self, start: usize, len: usize) -> Result<(), Error> {
+        if start >= self.size() {
+            return Err(Error::InvalidOffset);
+        }
+
+        if len > self.size() {
+            return Err(Error::InvalidSize);
+        }
+
+        debug_assert_eq!(self.upper.start(), self.lower.end());
+
+        Ok(())
+    }
+}
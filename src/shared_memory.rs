@@ -0,0 +1,268 @@
+//! This module implements anonymous shared memory on Linux using `memfd_create`.
+
+use crate::error::Error;
+use crate::mmap::PageSize;
+use bitflags::bitflags;
+use nix::fcntl::{FcntlArg, SealFlag, fcntl};
+use nix::sys::memfd::{MFdFlags, memfd_create};
+use std::fs::File;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+
+bitflags! {
+    /// The available flags to configure the shared memory.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub struct SharedMemoryFlags: u32 {
+        /// Closes the file descriptor when executing a new program.
+        const CLOEXEC       = 1 << 0;
+
+        /// Allows seals to be added to the shared memory. See [`SharedMemory::add_seals()`].
+        const ALLOW_SEALING = 1 << 1;
+
+        /// Backs the shared memory with huge pages.
+        const HUGE_PAGES    = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// The seals that restrict the operations that are allowed on shared memory.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub struct Seals: u32 {
+        /// Prevents any further seals from being added.
+        const SEAL         = 1 << 0;
+
+        /// Prevents the shared memory from shrinking.
+        const SHRINK       = 1 << 1;
+
+        /// Prevents the shared memory from growing.
+        const GROW         = 1 << 2;
+
+        /// Prevents the contents of the shared memory from being modified. This seal cannot be
+        /// added while the shared memory is mapped as shared, as such memory mappings may be made
+        /// writable at any time.
+        const WRITE        = 1 << 3;
+
+        /// Prevents the contents of the shared memory from being modified through any new shared
+        /// writable memory mappings, while existing ones remain writable. Since Linux 5.1.
+        const FUTURE_WRITE = 1 << 4;
+    }
+}
+
+impl From<Seals> for SealFlag {
+    fn from(seals: Seals) -> Self {
+        let mut flags = SealFlag::empty();
+
+        if seals.contains(Seals::SEAL) {
+            flags |= SealFlag::F_SEAL_SEAL;
+        }
+
+        if seals.contains(Seals::SHRINK) {
+            flags |= SealFlag::F_SEAL_SHRINK;
+        }
+
+        if seals.contains(Seals::GROW) {
+            flags |= SealFlag::F_SEAL_GROW;
+        }
+
+        if seals.contains(Seals::WRITE) {
+            flags |= SealFlag::F_SEAL_WRITE;
+        }
+
+        if seals.contains(Seals::FUTURE_WRITE) {
+            flags |= SealFlag::F_SEAL_FUTURE_WRITE;
+        }
+
+        flags
+    }
+}
+
+impl From<SealFlag> for Seals {
+    fn from(flags: SealFlag) -> Self {
+        let mut seals = Seals::empty();
+
+        if flags.contains(SealFlag::F_SEAL_SEAL) {
+            seals |= Seals::SEAL;
+        }
+
+        if flags.contains(SealFlag::F_SEAL_SHRINK) {
+            seals |= Seals::SHRINK;
+        }
+
+        if flags.contains(SealFlag::F_SEAL_GROW) {
+            seals |= Seals::GROW;
+        }
+
+        if flags.contains(SealFlag::F_SEAL_WRITE) {
+            seals |= Seals::WRITE;
+        }
+
+        if flags.contains(SealFlag::F_SEAL_FUTURE_WRITE) {
+            seals |= Seals::FUTURE_WRITE;
+        }
+
+        seals
+    }
+}
+
+/// Anonymous shared memory that lives in RAM and is referred to by a file descriptor. The file
+/// descriptor can be passed to other processes, e.g. over a Unix domain socket or by inheriting
+/// it across `fork()`, such that they can map the same memory.
+///
+/// The shared memory can be mapped using [`MmapOptions::with_shared_memory()`].
+///
+/// [`MmapOptions::with_shared_memory()`]: crate::MmapOptions::with_shared_memory
+#[derive(Debug)]
+pub struct SharedMemory {
+    file: File,
+}
+
+impl SharedMemory {
+    /// Creates new shared memory of the given size in bytes. The name is only used for debugging
+    /// purposes and shows up as `/memfd:<name>` in `/proc/self/maps`. Multiple instances of
+    /// shared memory may have the same name.
+    pub fn new(name: &str, size: usize, flags: SharedMemoryFlags) -> Result<Self, Error> {
+        Self::create(name, size, flags, None)
+    }
+
+    /// Creates new shared memory of the given size in bytes that is backed by huge pages of the
+    /// given page size. See [`SharedMemory::new()`] for more information.
+    pub fn new_with_page_size(
+        name: &str,
+        size: usize,
+        flags: SharedMemoryFlags,
+        page_size: PageSize,
+    ) -> Result<Self, Error> {
+        Self::create(
+            name,
+            size,
+            flags | SharedMemoryFlags::HUGE_PAGES,
+            Some(page_size),
+        )
+    }
+
+    fn create(
+        name: &str,
+        size: usize,
+        flags: SharedMemoryFlags,
+        page_size: Option<PageSize>,
+    ) -> Result<Self, Error> {
+        let mut memfd_flags = MFdFlags::empty();
+
+        if flags.contains(SharedMemoryFlags::CLOEXEC) {
+            memfd_flags |= MFdFlags::MFD_CLOEXEC;
+        }
+
+        if flags.contains(SharedMemoryFlags::ALLOW_SEALING) {
+            memfd_flags |= MFdFlags::MFD_ALLOW_SEALING;
+        }
+
+        if flags.contains(SharedMemoryFlags::HUGE_PAGES) {
+            memfd_flags |= MFdFlags::MFD_HUGETLB;
+        }
+
+        if let Some(page_size) = page_size {
+            memfd_flags |= match page_size {
+                PageSize::_64K => MFdFlags::from_bits_retain(libc::MFD_HUGE_64KB),
+                PageSize::_512K => MFdFlags::from_bits_retain(libc::MFD_HUGE_512KB),
+                PageSize::_1M => MFdFlags::MFD_HUGE_1MB,
+                PageSize::_2M => MFdFlags::MFD_HUGE_2MB,
+                PageSize::_8M => MFdFlags::MFD_HUGE_8MB,
+                PageSize::_16M => MFdFlags::MFD_HUGE_16MB,
+                PageSize::_32M => MFdFlags::MFD_HUGE_32MB,
+                PageSize::_256M => MFdFlags::MFD_HUGE_256MB,
+                PageSize::_512M => MFdFlags::MFD_HUGE_512MB,
+                PageSize::_1G => MFdFlags::MFD_HUGE_1GB,
+                PageSize::_2G => MFdFlags::MFD_HUGE_2GB,
+                PageSize::_16G => MFdFlags::MFD_HUGE_16GB,
+                _ => return Err(Error::InvalidSize),
+            };
+        }
+
+        let fd = memfd_create(name, memfd_flags)?;
+        let file = File::from(fd);
+
+        file.set_len(size as u64)?;
+
+        Ok(Self { file })
+    }
+
+    /// Returns the size of the shared memory in bytes.
+    pub fn len(&self) -> Result<u64, Error> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    /// Returns `true` if the shared memory is empty.
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Changes the size of the shared memory in bytes. This fails if the shared memory has been
+    /// sealed with [`Seals::GROW`] or [`Seals::SHRINK`].
+    pub fn set_len(&self, size: u64) -> Result<(), Error> {
+        self.file.set_len(size)?;
+
+        Ok(())
+    }
+
+    /// Adds the given seals to the shared memory. Seals cannot be removed once they have been
+    /// added, which allows other processes to trust that the shared memory will not change in the
+    /// ways prohibited by the seals. This requires the shared memory to have been created with
+    /// [`SharedMemoryFlags::ALLOW_SEALING`].
+    pub fn add_seals(&self, seals: Seals) -> Result<(), Error> {
+        fcntl(&self.file, FcntlArg::F_ADD_SEALS(seals.into()))?;
+
+        Ok(())
+    }
+
+    /// Returns the seals that have been added to the shared memory.
+    pub fn seals(&self) -> Result<Seals, Error> {
+        let flags = fcntl(&self.file, FcntlArg::F_GET_SEALS)?;
+
+        Ok(SealFlag::from_bits_truncate(flags).into())
+    }
+
+    /// Returns the [`File`] that refers to the shared memory.
+    #[inline]
+    pub fn as_file(&self) -> &File {
+        &self.file
+    }
+
+    /// Consumes the shared memory and returns the [`File`] that refers to it.
+    #[inline]
+    pub fn into_file(self) -> File {
+        self.file
+    }
+}
+
+impl AsRef<File> for SharedMemory {
+    fn as_ref(&self) -> &File {
+        &self.file
+    }
+}
+
+impl AsFd for SharedMemory {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+impl AsRawFd for SharedMemory {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl From<OwnedFd> for SharedMemory {
+    /// Wraps a file descriptor referring to shared memory, e.g. one that has been received from
+    /// another process.
+    fn from(fd: OwnedFd) -> Self {
+        Self {
+            file: File::from(fd),
+        }
+    }
+}
+
+impl From<SharedMemory> for OwnedFd {
+    fn from(shared_memory: SharedMemory) -> Self {
+        shared_memory.file.into()
+    }
+}
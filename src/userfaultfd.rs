@@ -0,0 +1,413 @@
+//! This module implements handling page faults in user space on Linux using `userfaultfd`.
+
+use crate::MmapOptions;
+use crate::error::Error;
+use bitflags::bitflags;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Range;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+
+mod sys {
+    const UFFDIO: u8 = 0xaa;
+
+    pub const UFFD_API: u64 = 0xaa;
+    pub const UFFD_USER_MODE_ONLY: libc::c_int = 1;
+    pub const UFFD_EVENT_PAGEFAULT: u8 = 0x12;
+
+    pub const UFFDIO_REGISTER_MODE_MISSING: u64 = 1 << 0;
+    pub const UFFDIO_REGISTER_MODE_WP: u64 = 1 << 1;
+    pub const UFFDIO_REGISTER_MODE_MINOR: u64 = 1 << 2;
+    pub const UFFDIO_WRITEPROTECT_MODE_WP: u64 = 1 << 0;
+
+    pub const UFFD_PAGEFAULT_FLAG_WRITE: u64 = 1 << 0;
+    pub const UFFD_PAGEFAULT_FLAG_WP: u64 = 1 << 1;
+    pub const UFFD_PAGEFAULT_FLAG_MINOR: u64 = 1 << 2;
+
+    #[repr(C)]
+    pub struct UffdioApi {
+        pub api: u64,
+        pub features: u64,
+        pub ioctls: u64,
+    }
+
+    #[repr(C)]
+    pub struct UffdioRange {
+        pub start: u64,
+        pub len: u64,
+    }
+
+    #[repr(C)]
+    pub struct UffdioRegister {
+        pub range: UffdioRange,
+        pub mode: u64,
+        pub ioctls: u64,
+    }
+
+    #[repr(C)]
+    pub struct UffdioCopy {
+        pub dst: u64,
+        pub src: u64,
+        pub len: u64,
+        pub mode: u64,
+        pub copy: i64,
+    }
+
+    #[repr(C)]
+    pub struct UffdioZeropage {
+        pub range: UffdioRange,
+        pub mode: u64,
+        pub zeropage: i64,
+    }
+
+    #[repr(C)]
+    pub struct UffdioWriteprotect {
+        pub range: UffdioRange,
+        pub mode: u64,
+    }
+
+    /// The layout of `struct uffd_msg`, where the event-specific arguments are kept as raw words.
+    #[repr(C)]
+    pub struct UffdMsg {
+        pub event: u8,
+        pub reserved1: u8,
+        pub reserved2: u16,
+        pub reserved3: u32,
+        pub arg: [u64; 3],
+    }
+
+    nix::ioctl_readwrite!(uffdio_api, UFFDIO, 0x3f, UffdioApi);
+    nix::ioctl_readwrite!(uffdio_register, UFFDIO, 0x00, UffdioRegister);
+    nix::ioctl_read!(uffdio_unregister, UFFDIO, 0x01, UffdioRange);
+    nix::ioctl_read!(uffdio_wake, UFFDIO, 0x02, UffdioRange);
+    nix::ioctl_readwrite!(uffdio_copy, UFFDIO, 0x03, UffdioCopy);
+    nix::ioctl_readwrite!(uffdio_zeropage, UFFDIO, 0x04, UffdioZeropage);
+    nix::ioctl_readwrite!(uffdio_writeprotect, UFFDIO, 0x06, UffdioWriteprotect);
+}
+
+bitflags! {
+    /// The available flags to configure the userfaultfd handler.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub struct UffdFlags: u32 {
+        /// Closes the file descriptor when executing a new program.
+        const CLOEXEC        = 1 << 0;
+
+        /// Makes [`UffdHandler::next_fault()`] fail rather than block when there are no pending
+        /// page faults. This is useful when polling the file descriptor.
+        const NONBLOCK       = 1 << 1;
+
+        /// Only handles page faults that originate from user space. This is allowed for
+        /// unprivileged processes, even if `vm.unprivileged_userfaultfd` is disabled. Since Linux
+        /// 5.11.
+        const USER_MODE_ONLY = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// The kinds of page faults to handle for a registered range.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub struct UffdRegisterMode: u32 {
+        /// Handles page faults on pages that are not present, which can then be populated using
+        /// [`UffdHandler::copy()`] or [`UffdHandler::zeropage()`].
+        const MISSING       = 1 << 0;
+
+        /// Handles page faults on writes to pages that have been write-protected using
+        /// [`UffdHandler::writeprotect()`]. Since Linux 5.7.
+        const WRITE_PROTECT = 1 << 1;
+
+        /// Handles page faults on pages that are present in the page cache, but not mapped, for
+        /// shared memory and huge pages. Since Linux 5.13.
+        const MINOR         = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// The flags that describe the cause of a page fault.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub struct PageFaultFlags: u32 {
+        /// The page fault was caused by a write.
+        const WRITE         = 1 << 0;
+
+        /// The page fault was caused by a write to a write-protected page.
+        const WRITE_PROTECT = 1 << 1;
+
+        /// The page fault was a minor fault.
+        const MINOR         = 1 << 2;
+    }
+}
+
+/// Describes a page fault that has been reported through userfaultfd.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PageFault {
+    /// The address that caused the page fault.
+    pub(crate) address: usize,
+    /// The flags that describe the cause of the page fault.
+    pub(crate) flags: PageFaultFlags,
+}
+
+impl PageFault {
+    /// The address that caused the page fault. This is rounded down to the page size.
+    #[inline]
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// The flags that describe the cause of the page fault.
+    #[inline]
+    pub fn flags(&self) -> PageFaultFlags {
+        self.flags
+    }
+}
+
+fn uffdio_range(range: &Range<usize>) -> Result<sys::UffdioRange, Error> {
+    let page_size = MmapOptions::page_size();
+
+    if range.start > range.end {
+        return Err(Error::InvalidOffset);
+    }
+
+    if range.start % page_size != 0 || range.end % page_size != 0 {
+        return Err(Error::InvalidAlignment);
+    }
+
+    Ok(sys::UffdioRange {
+        start: range.start as u64,
+        len: range.len() as u64,
+    })
+}
+
+/// A handler for page faults in user space using `userfaultfd`. Any address range of a memory
+/// mapping, e.g. `mapping.start()..mapping.end()` of an anonymous [`MmapMut`], can be registered
+/// with the handler. Any thread that then accesses a page in that range that matches the
+/// registered [`UffdRegisterMode`] is suspended until the page fault has been resolved, which is
+/// typically done by a background thread that loops over [`UffdHandler::next_fault()`].
+///
+/// Note that the handling thread itself must not access the registered range, as it would then
+/// wait on itself.
+///
+/// [`MmapMut`]: crate::MmapMut
+#[derive(Debug)]
+pub struct UffdHandler {
+    file: File,
+}
+
+impl UffdHandler {
+    /// Creates a new userfaultfd handler. This requires the `CAP_SYS_PTRACE` capability, unless
+    /// `vm.unprivileged_userfaultfd` is enabled or [`UffdFlags::USER_MODE_ONLY`] is set.
+    pub fn new(flags: UffdFlags) -> Result<Self, Error> {
+        let mut uffd_flags = 0;
+
+        if flags.contains(UffdFlags::CLOEXEC) {
+            uffd_flags |= libc::O_CLOEXEC;
+        }
+
+        if flags.contains(UffdFlags::NONBLOCK) {
+            uffd_flags |= libc::O_NONBLOCK;
+        }
+
+        if flags.contains(UffdFlags::USER_MODE_ONLY) {
+            uffd_flags |= sys::UFFD_USER_MODE_ONLY;
+        }
+
+        let fd = unsafe { libc::syscall(libc::SYS_userfaultfd, uffd_flags) };
+        let fd = nix::errno::Errno::result(fd)?;
+        let file = File::from(unsafe { OwnedFd::from_raw_fd(fd as RawFd) });
+
+        let mut api = sys::UffdioApi {
+            api: sys::UFFD_API,
+            features: 0,
+            ioctls: 0,
+        };
+
+        unsafe { sys::uffdio_api(file.as_raw_fd(), &mut api) }?;
+
+        Ok(Self { file })
+    }
+
+    /// Registers the given page aligned address range with the handler, such that the page faults
+    /// of the given modes are reported through [`UffdHandler::next_fault()`].
+    ///
+    /// # Safety
+    ///
+    /// The address range must be part of a memory mapping owned by the caller, e.g. an
+    /// [`MmapMut`] or [`ReservedMut`], and must not be unmapped while it is registered. Any thread
+    /// accessing the range blocks until its page faults have been resolved, so the caller must
+    /// ensure that they are resolved, and that the handling thread never accesses the range
+    /// itself.
+    ///
+    /// [`MmapMut`]: crate::MmapMut
+    /// [`ReservedMut`]: crate::ReservedMut
+    pub unsafe fn register(
+        &self,
+        range: Range<usize>,
+        mode: UffdRegisterMode,
+    ) -> Result<(), Error> {
+        let mut register_mode = 0;
+
+        if mode.contains(UffdRegisterMode::MISSING) {
+            register_mode |= sys::UFFDIO_REGISTER_MODE_MISSING;
+        }
+
+        if mode.contains(UffdRegisterMode::WRITE_PROTECT) {
+            register_mode |= sys::UFFDIO_REGISTER_MODE_WP;
+        }
+
+        if mode.contains(UffdRegisterMode::MINOR) {
+            register_mode |= sys::UFFDIO_REGISTER_MODE_MINOR;
+        }
+
+        let mut register = sys::UffdioRegister {
+            range: uffdio_range(&range)?,
+            mode: register_mode,
+            ioctls: 0,
+        };
+
+        unsafe { sys::uffdio_register(self.file.as_raw_fd(), &mut register) }?;
+
+        Ok(())
+    }
+
+    /// Unregisters the given page aligned address range from the handler. Any threads that are
+    /// waiting on page faults in this range are woken up.
+    pub fn unregister(&self, range: Range<usize>) -> Result<(), Error> {
+        let mut range = uffdio_range(&range)?;
+
+        unsafe { sys::uffdio_unregister(self.file.as_raw_fd(), &mut range) }?;
+
+        Ok(())
+    }
+
+    /// Waits for the next page fault in any of the registered ranges. If the handler has been
+    /// created with [`UffdFlags::NONBLOCK`] and there are no pending page faults, this returns an
+    /// error of kind [`std::io::ErrorKind::WouldBlock`].
+    pub fn next_fault(&self) -> Result<PageFault, Error> {
+        loop {
+            let mut bytes = [0u8; std::mem::size_of::<sys::UffdMsg>()];
+            (&self.file).read_exact(&mut bytes)?;
+
+            let msg: sys::UffdMsg = unsafe { std::mem::transmute(bytes) };
+
+            // Other events are only reported when requested during the API handshake.
+            if msg.event != sys::UFFD_EVENT_PAGEFAULT {
+                continue;
+            }
+
+            let mut flags = PageFaultFlags::empty();
+
+            if msg.arg[0] & sys::UFFD_PAGEFAULT_FLAG_WRITE != 0 {
+                flags |= PageFaultFlags::WRITE;
+            }
+
+            if msg.arg[0] & sys::UFFD_PAGEFAULT_FLAG_WP != 0 {
+                flags |= PageFaultFlags::WRITE_PROTECT;
+            }
+
+            if msg.arg[0] & sys::UFFD_PAGEFAULT_FLAG_MINOR != 0 {
+                flags |= PageFaultFlags::MINOR;
+            }
+
+            return Ok(PageFault {
+                address: msg.arg[1] as usize,
+                flags,
+            });
+        }
+    }
+
+    /// Resolves a missing page fault by atomically copying the given data to the pages starting at
+    /// the given page aligned address and waking up any threads waiting on them. The size of the
+    /// data must be a multiple of the page size. This fails if any of the pages are already
+    /// present.
+    ///
+    /// # Safety
+    ///
+    /// The pages must be part of a range that has been registered by the caller with
+    /// [`UffdHandler::register()`]. The data becomes visible to any references to the pages, so
+    /// the caller must ensure that this does not violate any invariants of their contents.
+    pub unsafe fn copy(&self, address: usize, data: &[u8]) -> Result<(), Error> {
+        let range = uffdio_range(&(address..address + data.len()))?;
+
+        let mut copy = sys::UffdioCopy {
+            dst: range.start,
+            src: data.as_ptr() as u64,
+            len: range.len,
+            mode: 0,
+            copy: 0,
+        };
+
+        unsafe { sys::uffdio_copy(self.file.as_raw_fd(), &mut copy) }?;
+
+        Ok(())
+    }
+
+    /// Resolves a missing page fault by mapping zeroed pages in the given page aligned address
+    /// range and waking up any threads waiting on them.
+    ///
+    /// # Safety
+    ///
+    /// See [`UffdHandler::copy()`], where the data consists of zero bytes.
+    pub unsafe fn zeropage(&self, range: Range<usize>) -> Result<(), Error> {
+        let mut zeropage = sys::UffdioZeropage {
+            range: uffdio_range(&range)?,
+            mode: 0,
+            zeropage: 0,
+        };
+
+        unsafe { sys::uffdio_zeropage(self.file.as_raw_fd(), &mut zeropage) }?;
+
+        Ok(())
+    }
+
+    /// Wakes up any threads waiting on page faults in the given page aligned address range, e.g.
+    /// after the page faults have been resolved by other means.
+    pub fn wake(&self, range: Range<usize>) -> Result<(), Error> {
+        let mut range = uffdio_range(&range)?;
+
+        unsafe { sys::uffdio_wake(self.file.as_raw_fd(), &mut range) }?;
+
+        Ok(())
+    }
+
+    /// Write-protects or unprotects the pages in the given page aligned address range, which must
+    /// have been registered with [`UffdRegisterMode::WRITE_PROTECT`]. Writes to write-protected
+    /// pages are reported as page faults with [`PageFaultFlags::WRITE_PROTECT`]. Unprotecting the
+    /// pages wakes up any threads waiting on them.
+    ///
+    /// # Safety
+    ///
+    /// The pages must be part of a range that has been registered by the caller with
+    /// [`UffdHandler::register()`]. Any thread writing to the write-protected pages blocks until
+    /// they have been unprotected, so the caller must ensure that these page faults are resolved,
+    /// and that the handling thread never writes to the pages itself.
+    pub unsafe fn writeprotect(&self, range: Range<usize>, protect: bool) -> Result<(), Error> {
+        let mut writeprotect = sys::UffdioWriteprotect {
+            range: uffdio_range(&range)?,
+            mode: if protect {
+                sys::UFFDIO_WRITEPROTECT_MODE_WP
+            } else {
+                0
+            },
+        };
+
+        unsafe { sys::uffdio_writeprotect(self.file.as_raw_fd(), &mut writeprotect) }?;
+
+        Ok(())
+    }
+}
+
+impl AsFd for UffdHandler {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+impl AsRawFd for UffdHandler {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl From<UffdHandler> for OwnedFd {
+    fn from(handler: UffdHandler) -> Self {
+        handler.file.into()
+    }
+}